@@ -89,11 +89,38 @@ impl FromStr for ResourceLocation {
     type Err = ResourceLocationError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let (namespace, path) = match s.find('c') {
-            Some(i) => s.split_at(i),
+        let (namespace, path) = match s.find(':') {
+            Some(i) => (&s[..i], &s[i + 1..]),
             None => ("minecraft", s),
         };
 
         Self::new(namespace, path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let location: ResourceLocation = "minecraft:stone".parse().unwrap();
+
+        assert_eq!(location.namespace(), "minecraft");
+        assert_eq!(location.path(), "stone");
+
+        let location: ResourceLocation = "coal_ore".parse().unwrap();
+
+        assert_eq!(location.namespace(), "minecraft");
+        assert_eq!(location.path(), "coal_ore");
+
+        let location: ResourceLocation = "custom:coal/block".parse().unwrap();
+
+        assert_eq!(location.namespace(), "custom");
+        assert_eq!(location.path(), "coal/block");
+        assert_eq!(location.to_string(), "custom:coal/block");
+
+        assert!("Minecraft:stone".parse::<ResourceLocation>().is_err());
+        assert!("minecraft:".parse::<ResourceLocation>().is_err());
+    }
+}
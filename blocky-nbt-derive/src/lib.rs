@@ -0,0 +1,253 @@
+use proc_macro2::TokenStream;
+use quote::{quote, format_ident};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, GenericArgument, LitStr, Path, PathArguments, Type};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Array {
+    Byte,
+    Int,
+    Long,
+}
+
+enum Fallback {
+    Default,
+    Function(Path),
+}
+
+struct Field {
+    ident: syn::Ident,
+    ty: Type,
+    name: String,
+    default: Option<Fallback>,
+    flatten: bool,
+    array: Option<Array>,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> syn::Result<Self> {
+        let ident = field.ident.clone().unwrap();
+        let mut result = Self {
+            name: ident.to_string(),
+            ident,
+            ty: field.ty.clone(),
+            default: None,
+            flatten: false,
+            array: None,
+        };
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("nbt")) {
+            attr.parse_nested_meta(|meta| {
+                let array = match meta.path.get_ident().map(|ident| ident.to_string()).as_deref() {
+                    Some("byte_array") => Some(Array::Byte),
+                    Some("int_array") => Some(Array::Int),
+                    Some("long_array") => Some(Array::Long),
+                    _ => None,
+                };
+
+                if array.is_some() {
+                    if result.array.is_some() {
+                        return Err(meta.error("only one array hint is allowed"));
+                    }
+
+                    result.array = array;
+                    return Ok(());
+                }
+
+                if meta.path.is_ident("rename") {
+                    result.name = meta.value()?.parse::<LitStr>()?.value();
+                } else if meta.path.is_ident("default") {
+                    result.default = Some(match meta.value() {
+                        Ok(value) => Fallback::Function(value.parse::<LitStr>()?.parse()?),
+                        Err(_) => Fallback::Default,
+                    });
+                } else if meta.path.is_ident("flatten") {
+                    result.flatten = true;
+                } else {
+                    return Err(meta.error("unknown nbt attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        if result.flatten && (result.default.is_some() || result.array.is_some()) {
+            return Err(Error::new_spanned(field, "flattened fields cannot have defaults or array hints"));
+        }
+
+        Ok(result)
+    }
+}
+
+// the T in Option<T> or Vec<T>, matched by name since that's all a derive can see
+fn inner_type<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let path = match ty {
+        Type::Path(ty) if ty.qself.is_none() => &ty.path,
+        _ => return None,
+    };
+
+    let segment = path.segments.last()?;
+
+    if segment.ident != name {
+        return None;
+    }
+
+    match &segment.arguments {
+        PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn to_tag(ty: &Type, value: TokenStream, array: Option<Array>) -> TokenStream {
+    match (array, inner_type(ty, "Vec")) {
+        (Some(Array::Byte), _) => quote!(::blocky_nbt::Tag::ByteArray(#value)),
+        (Some(Array::Int), _) => quote!(::blocky_nbt::Tag::IntArray(#value)),
+        (Some(Array::Long), _) => quote!(::blocky_nbt::Tag::LongArray(#value)),
+        (None, Some(inner)) => {
+            let inner = to_tag(inner, quote!(value), None);
+
+            quote!(::blocky_nbt::Tag::List(#value.into_iter().map(|value| #inner).collect()))
+        },
+        (None, None) => quote!(::blocky_nbt::Tag::from(#value)),
+    }
+}
+
+// builds an expression of type Result<ty, NbtError>
+fn from_tag(ty: &Type, tag: TokenStream, array: Option<Array>) -> TokenStream {
+    match (array, inner_type(ty, "Vec")) {
+        (None, Some(inner)) => {
+            let inner = from_tag(inner, quote!(tag), None);
+
+            quote! {
+                <::std::vec::Vec<::blocky_nbt::Tag> as ::std::convert::TryFrom<::blocky_nbt::Tag>>::try_from(#tag)
+                    .and_then(|v| v.into_iter().map(|tag| #inner).collect())
+            }
+        },
+        _ => quote!(<#ty as ::std::convert::TryFrom<::blocky_nbt::Tag>>::try_from(#tag)),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().map(Field::parse).collect::<syn::Result<Vec<_>>>()?,
+            _ => return Err(Error::new_spanned(&input, "NbtCompound needs a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input, "NbtCompound can only be derived for structs")),
+    };
+
+    // a flattened field reads every key left over, so a second one would read the same keys
+    if let Some(field) = fields.iter().filter(|field| field.flatten).nth(1) {
+        return Err(Error::new_spanned(&field.ident, "only one field can be flattened"));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut inserts = vec![];
+    let mut takes = vec![];
+    let mut flattens = vec![];
+
+    for field in &fields {
+        let Field { ident, ty, name, array, .. } = field;
+        let local = format_ident!("__field_{}", ident);
+
+        if field.flatten {
+            inserts.push(quote! {
+                if let ::blocky_nbt::Tag::Compound(flat) = ::blocky_nbt::Tag::from(value.#ident) {
+                    m.extend(flat);
+                }
+            });
+
+            // flattened fields see every key the named fields didn't take
+            flattens.push(quote! {
+                let #local = <#ty as ::std::convert::TryFrom<::blocky_nbt::Tag>>::try_from(::blocky_nbt::Tag::Compound(m.clone()))?;
+            });
+
+            continue;
+        }
+
+        let optional = inner_type(ty, "Option");
+        let value_ty = optional.unwrap_or(ty);
+        let insert = to_tag(value_ty, quote!(#local), *array);
+        let take = from_tag(value_ty, quote!(tag), *array);
+
+        let found = match optional {
+            Some(_) => quote!(::std::option::Option::Some(#take?)),
+            None => quote!(#take?),
+        };
+
+        let missing = match &field.default {
+            Some(Fallback::Function(path)) => quote!(#path()),
+            Some(Fallback::Default) => quote!(::std::default::Default::default()),
+            None if optional.is_some() => quote!(::std::option::Option::None),
+            None => quote!(return ::std::result::Result::Err(::blocky_nbt::NbtError::MissingField(#name))),
+        };
+
+        if optional.is_some() {
+            inserts.push(quote! {
+                if let ::std::option::Option::Some(#local) = value.#ident {
+                    m.insert(::std::string::String::from(#name), #insert);
+                }
+            });
+        } else {
+            inserts.push(quote! {
+                let #local = value.#ident;
+                m.insert(::std::string::String::from(#name), #insert);
+            });
+        }
+
+        takes.push(quote! {
+            let #local = match ::blocky_nbt::__private::take(&mut m, #name) {
+                ::std::option::Option::Some(tag) => #found,
+                ::std::option::Option::None => #missing,
+            };
+        });
+    }
+
+    let idents = fields.iter().map(|field| &field.ident);
+    let locals = fields.iter().map(|field| format_ident!("__field_{}", field.ident));
+
+    Ok(quote! {
+        impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::blocky_nbt::Tag #where_clause {
+            #[allow(unused_mut, unused_variables)]
+            fn from(value: #ident #ty_generics) -> Self {
+                let mut m = ::blocky_nbt::Map::new();
+
+                #(#inserts)*
+
+                ::blocky_nbt::Tag::Compound(m)
+            }
+        }
+
+        impl #impl_generics ::std::convert::TryFrom<::blocky_nbt::Tag> for #ident #ty_generics #where_clause {
+            type Error = ::blocky_nbt::NbtError;
+
+            #[allow(unused_mut, unused_variables)]
+            fn try_from(tag: ::blocky_nbt::Tag) -> ::std::result::Result<Self, Self::Error> {
+                let mut m = <::blocky_nbt::Map<::std::string::String, ::blocky_nbt::Tag> as ::std::convert::TryFrom<::blocky_nbt::Tag>>::try_from(tag)?;
+
+                #(#takes)*
+                #(#flattens)*
+
+                ::std::result::Result::Ok(Self {
+                    #(#idents: #locals,)*
+                })
+            }
+        }
+    })
+}
+
+// implements From<T> for Tag and TryFrom<Tag> for T, with each field as a key of a compound.
+// fields take #[nbt(rename = "Name")], #[nbt(default)] or #[nbt(default = "path")],
+// #[nbt(flatten)] on at most one field and #[nbt(byte_array)], #[nbt(int_array)] or
+// #[nbt(long_array)] to store a vec as an array instead of a list. option fields are left out
+// when none
+#[proc_macro_derive(NbtCompound, attributes(nbt))]
+pub fn derive_nbt_compound(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
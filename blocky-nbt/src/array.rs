@@ -0,0 +1,25 @@
+pub(crate) const BYTE_ARRAY: &str = "__blocky_nbt_byte_array";
+pub(crate) const INT_ARRAY: &str = "__blocky_nbt_int_array";
+pub(crate) const LONG_ARRAY: &str = "__blocky_nbt_long_array";
+
+// used as `#[serde(with = "blocky_nbt::int_array")]` to encode a field as an
+// array tag rather than a list
+macro_rules! array_module {
+    ($module:ident, $name:ident, $typ:ty) => {
+        pub mod $module {
+            use serde::{Serializer, Deserializer, Deserialize};
+
+            pub fn serialize<S: Serializer>(value: &[$typ], serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct(super::$name, value)
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<$typ>, D::Error> {
+                Vec::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+array_module!(byte_array, BYTE_ARRAY, i8);
+array_module!(int_array, INT_ARRAY, i32);
+array_module!(long_array, LONG_ARRAY, i64);
@@ -0,0 +1,217 @@
+use crate::{Tag, Map, NbtError};
+use blocky_core::ResourceLocation;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+// quarter turns clockwise, seen from above
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    pub(crate) fn turns(self) -> usize {
+        match self {
+            Self::None => 0,
+            Self::Clockwise90 => 1,
+            Self::Clockwise180 => 2,
+            Self::Counterclockwise90 => 3,
+        }
+    }
+}
+
+// named like vanilla, where left_right flips along z and front_back flips along x
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirror {
+    None,
+    LeftRight,
+    FrontBack,
+}
+
+const HORIZONTAL: [&str; 4] = ["north", "east", "south", "west"];
+
+fn rotate_direction(direction: &str, rotation: Rotation) -> Option<&'static str> {
+    let i = HORIZONTAL.iter().position(|d| *d == direction)?;
+
+    Some(HORIZONTAL[(i + rotation.turns()) % 4])
+}
+
+fn mirror_direction(direction: &str, mirror: Mirror) -> Option<&'static str> {
+    Some(match (direction, mirror) {
+        ("north", Mirror::LeftRight) => "south",
+        ("south", Mirror::LeftRight) => "north",
+        ("east", Mirror::FrontBack) => "west",
+        ("west", Mirror::FrontBack) => "east",
+        _ => HORIZONTAL.iter().find(|d| **d == direction)?,
+    })
+}
+
+// a block and its properties, as stored in structure and chunk palettes
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockState {
+    pub name: ResourceLocation,
+    pub properties: Map<String, String>,
+}
+
+impl BlockState {
+    pub fn new(name: ResourceLocation) -> Self {
+        Self {
+            name,
+            properties: Map::new(),
+        }
+    }
+
+    pub fn with_property<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.properties.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn property(&self, name: &str) -> Option<&str> {
+        self.properties.get(name).map(String::as_str)
+    }
+
+    pub fn from_tag(tag: &Tag) -> Result<Self, NbtError> {
+        let name = match tag.get("Name") {
+            Some(name) => <&str>::try_from(name)?,
+            None => return Err(NbtError::MissingField("Name")),
+        };
+
+        let name = name.parse()
+            .map_err(|_| NbtError::InvalidResourceLocation(name.to_owned()))?;
+
+        let mut properties = Map::new();
+
+        if let Some(tag) = tag.get("Properties") {
+            let m = tag.as_compound().ok_or_else(|| tag.wrong_type("compound"))?;
+
+            for (key, value) in m {
+                properties.insert(key.clone(), <&str>::try_from(value)?.to_owned());
+            }
+        }
+
+        Ok(Self { name, properties })
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("Name".to_owned(), Tag::String(self.name.to_string()));
+
+        if !self.properties.is_empty() {
+            let properties = self.properties.iter()
+                .map(|(key, value)| (key.clone(), Tag::String(value.clone())))
+                .collect();
+
+            m.insert("Properties".to_owned(), Tag::Compound(properties));
+        }
+
+        Tag::Compound(m)
+    }
+
+    // covers facing, axis, the sixteen step rotation and north, east, south and west
+    // connections, leaving shapes like those of stairs and rails as they are
+    pub fn rotate(&mut self, rotation: Rotation) {
+        if rotation == Rotation::None {
+            return;
+        }
+
+        let mut properties = Map::new();
+
+        for (key, value) in &self.properties {
+            let key = rotate_direction(key, rotation).map(str::to_owned).unwrap_or_else(|| key.clone());
+            let changed = match key.as_str() {
+                "facing" => rotate_direction(value, rotation).map(str::to_owned),
+                "axis" if rotation.turns() % 2 == 1 => match value.as_str() {
+                    "x" => Some("z".to_owned()),
+                    "z" => Some("x".to_owned()),
+                    _ => None,
+                },
+                "rotation" => value.parse::<usize>()
+                    .ok()
+                    .map(|r| ((r + rotation.turns() * 4) % 16).to_string()),
+                _ => None,
+            };
+
+            properties.insert(key, changed.unwrap_or_else(|| value.clone()));
+        }
+
+        self.properties = properties;
+    }
+
+    pub fn mirror(&mut self, mirror: Mirror) {
+        if mirror == Mirror::None {
+            return;
+        }
+
+        let mut properties = Map::new();
+
+        for (key, value) in &self.properties {
+            let key = mirror_direction(key, mirror).map(str::to_owned).unwrap_or_else(|| key.clone());
+            let changed = match key.as_str() {
+                "facing" => mirror_direction(value, mirror).map(str::to_owned),
+                "rotation" => value.parse::<usize>().ok().filter(|r| *r < 16).map(|r| match mirror {
+                    Mirror::LeftRight => (24 - r) % 16,
+                    _ => (16 - r) % 16,
+                }.to_string()),
+                _ => None,
+            };
+
+            properties.insert(key, changed.unwrap_or_else(|| value.clone()));
+        }
+
+        self.properties = properties;
+    }
+}
+
+// the format used by commands, like minecraft:oak_stairs[facing=north,half=top], with the
+// properties sorted by name like vanilla
+impl fmt::Display for BlockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+
+        if !self.properties.is_empty() {
+            let mut properties: Vec<_> = self.properties.iter().collect();
+
+            properties.sort_by_key(|(key, _)| *key);
+
+            let properties: Vec<_> = properties.iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+
+            write!(f, "[{}]", properties.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for BlockState {
+    type Err = NbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || NbtError::InvalidBlockState(s.to_owned());
+
+        let (name, properties) = match s.find('[') {
+            Some(i) if s.ends_with(']') => (&s[..i], Some(&s[i + 1..s.len() - 1])),
+            Some(_) => return Err(invalid()),
+            None => (s, None),
+        };
+
+        let mut state = Self::new(name.parse().map_err(|_| invalid())?);
+
+        for property in properties.into_iter().flat_map(|p| p.split(',')).filter(|p| !p.is_empty()) {
+            let (key, value) = match property.find('=') {
+                Some(i) => (&property[..i], &property[i + 1..]),
+                None => return Err(invalid()),
+            };
+
+            state.properties.insert(key.trim().to_owned(), value.trim().to_owned());
+        }
+
+        Ok(state)
+    }
+}
@@ -0,0 +1,325 @@
+use crate::{Nbt, Tag, Map, List, NbtError, BlockState, PackedArray, PalettedContainer, Packing};
+use crate::__private::{take, get};
+use blocky_core::ResourceLocation;
+use std::convert::TryFrom;
+
+// the first data version to keep sections at the root instead of under Level, with biomes
+// stored per section
+pub const CHUNK_ROOT_VERSION: i32 = 2844;
+
+const SECTION_BLOCKS: usize = 4096;
+const SECTION_BIOMES: usize = 64;
+
+// the narrowest indices vanilla writes for palettes of more than one entry
+const BLOCK_STATE_BITS: u32 = 4;
+const BIOME_BITS: u32 = 1;
+
+fn air() -> BlockState {
+    BlockState::new(ResourceLocation::new("minecraft", "air").unwrap())
+}
+
+// the section holding a block and the block's index in it, with x and z taken within the chunk.
+// none where y is below or above any section y can be
+fn locate(x: i32, y: i32, z: i32) -> Option<(i8, usize)> {
+    let section = i8::try_from(y.div_euclid(16)).ok()?;
+    let index = (y.rem_euclid(16) * 256 + (z & 15) * 16 + (x & 15)) as usize;
+
+    Some((section, index))
+}
+
+// sixteen blocks of height. sections holding only light have no block states, and sections
+// from before 1.18 keep their biomes in the chunk instead
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub y: i8,
+    pub block_states: Option<PalettedContainer<BlockState>>,
+    pub biomes: Option<PalettedContainer<ResourceLocation>>,
+    pub block_light: Option<Vec<i8>>,
+    pub sky_light: Option<Vec<i8>>,
+    pub extra: Map<String, Tag>,
+}
+
+impl Section {
+    fn from_tag(tag: Tag, data_version: i32) -> Result<Self, NbtError> {
+        let mut m = Map::try_from(tag)?;
+        let y = get(&mut m, "Y")?.ok_or(NbtError::MissingField("Y"))?;
+
+        let (block_states, biomes) = if data_version >= CHUNK_ROOT_VERSION {
+            let block_states = take(&mut m, "block_states")
+                .map(|tag| PalettedContainer::from_tag(&tag, SECTION_BLOCKS, BLOCK_STATE_BITS))
+                .transpose()?;
+
+            let biomes = take(&mut m, "biomes")
+                .map(|tag| PalettedContainer::from_tag(&tag, SECTION_BIOMES, BIOME_BITS))
+                .transpose()?;
+
+            (block_states, biomes)
+        } else if m.contains_key("Palette") && m.contains_key("BlockStates") {
+            let palette = take(&mut m, "Palette").unwrap();
+            let data = take(&mut m, "BlockStates").unwrap();
+            let packing = Packing::for_version(data_version);
+
+            (Some(PalettedContainer::from_lists(&palette, &data, SECTION_BLOCKS, BLOCK_STATE_BITS, packing)?), None)
+        } else {
+            (None, None)
+        };
+
+        Ok(Self {
+            y,
+            block_states,
+            biomes,
+            block_light: get(&mut m, "BlockLight")?,
+            sky_light: get(&mut m, "SkyLight")?,
+            extra: m,
+        })
+    }
+
+    fn to_tag(&self, data_version: i32) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("Y".to_owned(), Tag::Byte(self.y));
+
+        match &self.block_states {
+            Some(states) if data_version >= CHUNK_ROOT_VERSION => {
+                m.insert("block_states".to_owned(), states.to_tag());
+            },
+            Some(states) => {
+                m.insert("Palette".to_owned(), states.palette_tag());
+                m.insert("BlockStates".to_owned(), states.data().to_tag());
+            },
+            None => {},
+        }
+
+        if let Some(biomes) = &self.biomes {
+            m.insert("biomes".to_owned(), biomes.to_tag());
+        }
+
+        if let Some(light) = &self.block_light {
+            m.insert("BlockLight".to_owned(), Tag::ByteArray(light.clone()));
+        }
+
+        if let Some(light) = &self.sky_light {
+            m.insert("SkyLight".to_owned(), Tag::ByteArray(light.clone()));
+        }
+
+        for (key, value) in &self.extra {
+            m.insert(key.clone(), value.clone());
+        }
+
+        Tag::Compound(m)
+    }
+}
+
+// a chunk as stored in region files, in the layout of 1.18 and later or the Level compound
+// used from 1.13 to 1.17, picked by data version. older chunks have no palettes, so their
+// sections read without block states
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub data_version: i32,
+    pub x: i32,
+    pub z: i32,
+    // the lowest section, since 1.18
+    pub y: Option<i32>,
+    pub status: Option<String>,
+    pub sections: Vec<Section>,
+    pub block_entities: Vec<Map<String, Tag>>,
+    // the packed longs of each heightmap, whose width depends on the world height
+    pub heightmaps: Map<String, Vec<i64>>,
+    // keys this model doesn't know about, written back unchanged, from Level in older chunks
+    pub extra: Map<String, Tag>,
+}
+
+impl Chunk {
+    pub fn from_tag(tag: Tag) -> Result<Self, NbtError> {
+        let mut root = Map::try_from(tag)?;
+        let data_version = get(&mut root, "DataVersion")?.unwrap_or_default();
+
+        let (mut m, sections, block_entities) = if data_version >= CHUNK_ROOT_VERSION {
+            (root, "sections", "block_entities")
+        } else {
+            (get(&mut root, "Level")?.ok_or(NbtError::MissingField("Level"))?, "Sections", "TileEntities")
+        };
+
+        let sections = match get::<List>(&mut m, sections)? {
+            Some(sections) => sections.into_iter()
+                .map(|tag| Section::from_tag(tag, data_version))
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+
+        let block_entities = match get::<List>(&mut m, block_entities)? {
+            Some(entities) => entities.into_iter()
+                .map(Map::try_from)
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+
+        let mut heightmaps = Map::new();
+
+        if let Some(maps) = get::<Map<String, Tag>>(&mut m, "Heightmaps")? {
+            for (name, data) in maps {
+                heightmaps.insert(name, Vec::try_from(data)?);
+            }
+        }
+
+        Ok(Self {
+            data_version,
+            x: get(&mut m, "xPos")?.ok_or(NbtError::MissingField("xPos"))?,
+            z: get(&mut m, "zPos")?.ok_or(NbtError::MissingField("zPos"))?,
+            y: get(&mut m, "yPos")?,
+            status: get(&mut m, "Status")?,
+            sections,
+            block_entities,
+            heightmaps,
+            extra: m,
+        })
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        let modern = self.data_version >= CHUNK_ROOT_VERSION;
+        let mut m = Map::new();
+
+        m.insert("xPos".to_owned(), Tag::Int(self.x));
+        m.insert("zPos".to_owned(), Tag::Int(self.z));
+
+        if let Some(y) = self.y {
+            m.insert("yPos".to_owned(), Tag::Int(y));
+        }
+
+        if let Some(status) = &self.status {
+            m.insert("Status".to_owned(), Tag::String(status.clone()));
+        }
+
+        let sections: Vec<_> = self.sections.iter().map(|section| section.to_tag(self.data_version)).collect();
+        let block_entities: Vec<_> = self.block_entities.iter().cloned().map(Tag::Compound).collect();
+
+        // like vanilla, empty lists are written without a type
+        m.insert(if modern { "sections" } else { "Sections" }.to_owned(), Tag::from(sections));
+        m.insert(if modern { "block_entities" } else { "TileEntities" }.to_owned(), Tag::from(block_entities));
+
+        let heightmaps = self.heightmaps.iter()
+            .map(|(name, data)| (name.clone(), Tag::LongArray(data.clone())))
+            .collect();
+
+        m.insert("Heightmaps".to_owned(), Tag::Compound(heightmaps));
+
+        for (key, value) in &self.extra {
+            m.insert(key.clone(), value.clone());
+        }
+
+        if modern {
+            m.insert("DataVersion".to_owned(), Tag::Int(self.data_version));
+
+            Tag::Compound(m)
+        } else {
+            let mut root = Map::new();
+
+            root.insert("DataVersion".to_owned(), Tag::Int(self.data_version));
+            root.insert("Level".to_owned(), Tag::Compound(m));
+
+            Tag::Compound(root)
+        }
+    }
+
+    pub fn from_nbt(nbt: Nbt) -> Result<Self, NbtError> {
+        Self::from_tag(nbt.tag)
+    }
+
+    pub fn to_nbt(&self) -> Nbt {
+        Nbt::new(String::new(), self.to_tag())
+    }
+
+    pub fn packing(&self) -> Packing {
+        Packing::for_version(self.data_version)
+    }
+
+    pub fn section(&self, y: i8) -> Option<&Section> {
+        self.sections.iter().find(|section| section.y == y)
+    }
+
+    pub fn section_mut(&mut self, y: i8) -> Option<&mut Section> {
+        self.sections.iter_mut().find(|section| section.y == y)
+    }
+
+    // world_height is the number of blocks from the bottom of the world to the top, like
+    // 384 for the overworld since 1.18
+    pub fn heightmap(&self, name: &str, world_height: u32) -> Option<Result<PackedArray, NbtError>> {
+        let data = self.heightmaps.get(name)?;
+
+        Some(PackedArray::heightmap(&Tag::LongArray(data.clone()), world_height, self.packing()))
+    }
+
+    pub fn set_heightmap<S: Into<String>>(&mut self, name: S, heightmap: PackedArray) {
+        self.heightmaps.insert(name.into(), heightmap.into_longs());
+    }
+
+    // none where no section stores block states. x and z are taken within the chunk, so world
+    // coordinates work as well
+    pub fn block(&self, x: i32, y: i32, z: i32) -> Option<&BlockState> {
+        let (section, index) = locate(x, y, z)?;
+
+        Some(self.section(section)?.block_states.as_ref()?.get(index))
+    }
+
+    // returns the block that was stored there, filling a section that had no block states with
+    // air first. a block entity at the position is removed, since it belonged to the old block.
+    // fails where y is outside the range of sections
+    //
+    // the section's light is dropped and isLightOn removed, so the game lights the chunk again
+    // when it loads. heightmaps are left as they are and need recomputing when the highest block
+    // of a column changes
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: BlockState) -> Result<Option<BlockState>, NbtError> {
+        let (section, index) = locate(x, y, z).ok_or(NbtError::HeightOutOfRange(y))?;
+        let modern = self.data_version >= CHUNK_ROOT_VERSION;
+        let packing = self.packing();
+
+        if self.section(section).is_none() {
+            let i = self.sections.iter().position(|s| s.y > section).unwrap_or(self.sections.len());
+
+            self.sections.insert(i, Section {
+                y: section,
+                block_states: None,
+                biomes: None,
+                block_light: None,
+                sky_light: None,
+                extra: Map::new(),
+            });
+        }
+
+        let section = self.section_mut(section).unwrap();
+
+        // vanilla reads missing biomes as plains
+        if modern && section.biomes.is_none() {
+            let plains = ResourceLocation::new("minecraft", "plains").unwrap();
+
+            section.biomes = Some(PalettedContainer::new(SECTION_BIOMES, plains, BIOME_BITS, packing).with_single_value(true));
+        }
+
+        section.block_light = None;
+        section.sky_light = None;
+
+        let old = match &mut section.block_states {
+            Some(states) => Some(states.set(index, state)),
+            None => {
+                let mut states = PalettedContainer::new(SECTION_BLOCKS, air(), BLOCK_STATE_BITS, packing).with_single_value(modern);
+
+                states.set(index, state);
+                section.block_states = Some(states);
+
+                None
+            },
+        };
+
+        take(&mut self.extra, "isLightOn");
+
+        let (x, z) = (self.x * 16 + (x & 15), self.z * 16 + (z & 15));
+
+        self.block_entities.retain(|entity| {
+            let pos = (entity.get("x"), entity.get("y"), entity.get("z"));
+
+            pos != (Some(&Tag::Int(x)), Some(&Tag::Int(y)), Some(&Tag::Int(z)))
+        });
+
+        Ok(old)
+    }
+}
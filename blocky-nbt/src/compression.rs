@@ -0,0 +1,24 @@
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zlib,
+    Deflate,
+    Lz4,
+    // sniffs gzip, zlib and lz4 headers when decoding, falling back to uncompressed,
+    // and writes gzip when encoding since that is what standalone files use
+    #[default]
+    Auto,
+}
+
+impl Compression {
+    pub fn detect(header: &[u8]) -> Self {
+        match header {
+            [0x1f, 0x8b, ..] => Self::Gzip,
+            _ if header.starts_with(crate::lz4::MAGIC) => Self::Lz4,
+            // zlib headers are a deflate method byte whose check bits make the pair divisible by 31
+            [cmf, flg, ..] if cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) => Self::Zlib,
+            _ => Self::None,
+        }
+    }
+}
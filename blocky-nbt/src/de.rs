@@ -0,0 +1,219 @@
+use crate::{Nbt, Tag, Map, NbtError};
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use std::io::Read;
+
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, NbtError> {
+    T::deserialize(tag)
+}
+
+// reads data in the format Nbt::decode reads, ignoring the root name
+pub fn from_reader<R: Read, T: DeserializeOwned>(src: &mut R) -> Result<T, NbtError> {
+    let nbt = Nbt::decode(src)?;
+
+    from_tag(nbt.tag)
+}
+
+macro_rules! deserialize_unsigned {
+    ($method:ident, $visit:ident, $output:ident, $typ:ty) => {
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+            match self {
+                Tag::$output(value) => visitor.$visit(value as $typ),
+                tag => tag.deserialize_any(visitor),
+            }
+        }
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = NbtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::End => visitor.visit_unit(),
+
+            Tag::Byte(value) => visitor.visit_i8(value),
+            Tag::Short(value) => visitor.visit_i16(value),
+            Tag::Int(value) => visitor.visit_i32(value),
+            Tag::Long(value) => visitor.visit_i64(value),
+
+            Tag::Float(value) => visitor.visit_f32(value),
+            Tag::Double(value) => visitor.visit_f64(value),
+
+            Tag::String(s) => visitor.visit_string(s),
+
+            Tag::List(v) => visitor.visit_seq(v.into_vec().into_deserializer()),
+            Tag::Compound(m) => visitor.visit_map(MapAccess::new(m)),
+
+            Tag::ByteArray(v) => visitor.visit_seq(v.into_deserializer()),
+            Tag::IntArray(v) => visitor.visit_seq(v.into_deserializer()),
+            Tag::LongArray(v) => visitor.visit_seq(v.into_deserializer()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::Byte(value) => visitor.visit_bool(value != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_unsigned!(deserialize_u8, visit_u8, Byte, u8);
+    deserialize_unsigned!(deserialize_u16, visit_u16, Short, u16);
+    deserialize_unsigned!(deserialize_u32, visit_u32, Int, u32);
+    deserialize_unsigned!(deserialize_u64, visit_u64, Long, u64);
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::ByteArray(v) => visitor.visit_byte_buf(v.into_iter().map(|b| b as u8).collect()),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::End => visitor.visit_none(),
+            tag => visitor.visit_some(tag),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(m) if m.len() == 1 => {
+                let (variant, value) = m.into_iter().next().unwrap();
+
+                visitor.visit_enum(EnumAccess { variant, value })
+            },
+            _ => Err(NbtError::Message(String::from("expected string or single entry compound for enum"))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string
+        unit seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, NbtError> for Tag {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+struct MapAccess {
+    iter: <Map<String, Tag> as IntoIterator>::IntoIter,
+    value: Option<Tag>,
+}
+
+impl MapAccess {
+    fn new(m: Map<String, Tag>) -> Self {
+        Self {
+            iter: m.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = NbtError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, NbtError>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, NbtError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(NbtError::Message(String::from("value requested before key"))),
+        }
+    }
+}
+
+struct EnumAccess {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = NbtError;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantAccess), NbtError>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let deserializer: de::value::StringDeserializer<NbtError> = self.variant.into_deserializer();
+        let variant = seed.deserialize(deserializer)?;
+
+        Ok((variant, VariantAccess(self.value)))
+    }
+}
+
+struct VariantAccess(Tag);
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = NbtError;
+
+    fn unit_variant(self) -> Result<(), NbtError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, NbtError>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.0)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, NbtError> {
+        de::Deserializer::deserialize_seq(self.0, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        de::Deserializer::deserialize_map(self.0, visitor)
+    }
+}
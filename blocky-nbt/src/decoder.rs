@@ -1,150 +1,518 @@
-use crate::{Nbt, Tag, Map};
-use flate2::read::GzDecoder;
-use bytes::{Bytes, Buf};
-use std::io::Read;
-use std::str;
-
-fn decode_tag(buf: &mut Bytes, id: u8) -> anyhow::Result<Tag> {
-    match id {
-        0 => Ok(Tag::End),
-
-        1 => Ok(Tag::Byte(buf.get_i8())),
-        2 => Ok(Tag::Short(buf.get_i16())),
-        3 => Ok(Tag::Int(buf.get_i32())),
-        4 => Ok(Tag::Long(buf.get_i64())),
-
-        5 => Ok(Tag::Float(buf.get_f32())),
-        6 => Ok(Tag::Double(buf.get_f64())),
-
-        8 => {
-            let len = buf.get_i16() as usize;
-            let s = match str::from_utf8(&buf.bytes()[0..len]) {
-                Ok(s) => s.to_owned(),
-                Err(_) => anyhow::bail!("failed to decode string"),
-            };
-
-            // skip string bytes
-            buf.advance(len);
-
-            // return string nbt
-            Ok(Tag::String(s))
-        },
-
-        9 => {
-            let tag_id = buf.get_u8();
-            let len = buf.get_i32();
-            let mut v = vec![];
-
-            for _ in 0..len {
-                v.push(decode_tag(buf, tag_id)?);
-            }
+use crate::{Nbt, Tag, List, Map, Compression, Dialect, NbtError, DecodeError, PathSegment};
+use crate::tag::path_to_string;
+use crate::lz4::Lz4Reader;
+use crate::mutf8;
+use flate2::bufread::{GzDecoder, ZlibDecoder, DeflateDecoder};
+use std::io::{self, Read, BufRead, BufReader};
+
+// counts bytes pulled from the underlying source so progress can be reported
+struct Counter<R> {
+    inner: R,
+    count: u64,
+    progress: Option<Box<dyn FnMut(u64)>>,
+}
 
-            Ok(Tag::List(v))
-        },
+impl<R: Read> Read for Counter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
 
-        10 => {
-            let mut m = Map::new();
+        self.count += n as u64;
 
-            loop {
-                let nbt = decode_nbt(buf)?;
+        if let Some(progress) = self.progress.as_mut() {
+            progress(self.count);
+        }
 
-                if nbt.tag == Tag::End {
-                    break;
-                }
+        Ok(n)
+    }
+}
 
-                m.insert(nbt.name, nbt.tag);
-            }
+enum Stream<R> {
+    // explicitly uncompressed sources aren't buffered, so nothing past the nbt is consumed
+    Raw(Counter<R>),
+    Plain(BufReader<Counter<R>>),
+    Gzip(GzDecoder<BufReader<Counter<R>>>),
+    Zlib(ZlibDecoder<BufReader<Counter<R>>>),
+    Deflate(DeflateDecoder<BufReader<Counter<R>>>),
+    Lz4(Lz4Reader<BufReader<Counter<R>>>),
+}
 
-            Ok(Tag::Compound(m))
-        },
+impl<R> Stream<R> {
+    fn counter(&self) -> &Counter<R> {
+        match self {
+            Self::Raw(counter) => counter,
+            Self::Plain(reader) => reader.get_ref(),
+            Self::Gzip(gz) => gz.get_ref().get_ref(),
+            Self::Zlib(zlib) => zlib.get_ref().get_ref(),
+            Self::Deflate(deflate) => deflate.get_ref().get_ref(),
+            Self::Lz4(lz4) => lz4.get_ref().get_ref(),
+        }
+    }
 
-        7 => {
-            let len = buf.get_i32();
-            let mut v = vec![];
+    fn counter_mut(&mut self) -> &mut Counter<R> {
+        match self {
+            Self::Raw(counter) => counter,
+            Self::Plain(reader) => reader.get_mut(),
+            Self::Gzip(gz) => gz.get_mut().get_mut(),
+            Self::Zlib(zlib) => zlib.get_mut().get_mut(),
+            Self::Deflate(deflate) => deflate.get_mut().get_mut(),
+            Self::Lz4(lz4) => lz4.get_mut().get_mut(),
+        }
+    }
+}
+
+impl<R: Read> Read for Stream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Raw(counter) => counter.read(buf),
+            Self::Plain(reader) => reader.read(buf),
+            Self::Gzip(gz) => gz.read(buf),
+            Self::Zlib(zlib) => zlib.read(buf),
+            Self::Deflate(deflate) => deflate.read(buf),
+            Self::Lz4(lz4) => lz4.read(buf),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub max_depth: usize,
+    pub max_size: u64,
+    pub max_array_len: usize,
+}
 
-            for _ in 0..len {
-                v.push(buf.get_i8());
+impl Limits {
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: usize::MAX,
+            max_size: u64::MAX,
+            max_array_len: usize::MAX,
+        }
+    }
+
+    // matches the quota vanilla applies to nbt received from clients
+    pub fn network() -> Self {
+        Self {
+            max_size: 2 * 1024 * 1024,
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_depth: 512,
+            max_size: u64::MAX,
+            max_array_len: i32::MAX as usize,
+        }
+    }
+}
+
+// most elements reserved up front, so a hostile length can't force a huge allocation
+const MAX_PREALLOC: usize = 4096;
+
+pub struct Decoder<R> {
+    stream: Stream<R>,
+    limits: Limits,
+    dialect: Dialect,
+    pub(crate) network: bool,
+    pub(crate) position: u64,
+    pub(crate) path: Vec<PathSegment>,
+}
+
+macro_rules! read_num {
+    ($name:ident, $typ:ty) => {
+        pub(crate) fn $name(&mut self) -> Result<$typ, NbtError> {
+            let mut bytes = [0; std::mem::size_of::<$typ>()];
+
+            self.read_bytes(&mut bytes)?;
+
+            if self.dialect.is_little_endian() {
+                Ok(<$typ>::from_le_bytes(bytes))
+            } else {
+                Ok(<$typ>::from_be_bytes(bytes))
             }
+        }
+    };
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(src: R, compression: Compression) -> Result<Self, NbtError> {
+        let counter = Counter {
+            inner: src,
+            count: 0,
+            progress: None,
+        };
+
+        if compression == Compression::None {
+            return Ok(Self::from_stream(Stream::Raw(counter)));
+        }
+
+        let mut reader = BufReader::new(counter);
+
+        let compression = match compression {
+            // peek at the header to see how the source is compressed
+            Compression::Auto => Compression::detect(reader.fill_buf()?),
+            compression => compression,
+        };
+
+        let stream = match compression {
+            Compression::Gzip => Stream::Gzip(GzDecoder::new(reader)),
+            Compression::Zlib => Stream::Zlib(ZlibDecoder::new(reader)),
+            Compression::Deflate => Stream::Deflate(DeflateDecoder::new(reader)),
+            Compression::Lz4 => Stream::Lz4(Lz4Reader::new(reader)),
+            _ => Stream::Plain(reader),
+        };
+
+        Ok(Self::from_stream(stream))
+    }
+
+    fn from_stream(stream: Stream<R>) -> Self {
+        Self {
+            stream,
+            limits: Limits::default(),
+            dialect: Dialect::Java,
+            network: false,
+            position: 0,
+            path: vec![],
+        }
+    }
+
+    pub fn with_progress<F: FnMut(u64) + 'static>(mut self, mut progress: F) -> Self {
+        let counter = self.stream.counter_mut();
+
+        // report any header bytes already read by `new`
+        progress(counter.count);
+        counter.progress = Some(Box::new(progress));
+
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    // the network variant used by the protocol since 1.20.2 has no root name
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.stream.counter().count
+    }
+
+    pub fn decode(&mut self) -> Result<Nbt, NbtError> {
+        self.position = 0;
+        self.path.clear();
+
+        if self.network {
+            let id = self.read_u8()?;
+
+            self.path.push(PathSegment::Key(String::new()));
+
+            Ok(Nbt::new(String::new(), self.decode_tag(id, 0)?))
+        } else {
+            self.decode_nbt(0)
+        }
+    }
+
+    pub(crate) fn error(&self, kind: DecodeError) -> NbtError {
+        // skip the root name so paths start at the root's children
+        let path = path_to_string(self.path.get(1..).unwrap_or(&[]));
+
+        NbtError::Decode {
+            kind,
+            offset: self.position,
+            path,
+        }
+    }
+
+    fn read_bytes(&mut self, buf: &mut [u8]) -> Result<(), NbtError> {
+        let len = buf.len() as u64;
+
+        if self.position + len > self.limits.max_size {
+            return Err(self.error(DecodeError::SizeLimit(self.limits.max_size)));
+        }
+
+        match self.stream.read_exact(buf) {
+            Ok(()) => {
+                self.position += len;
+                Ok(())
+            },
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(self.error(DecodeError::UnexpectedEof))
+            },
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    read_num!(read_u8, u8);
+    read_num!(read_i8, i8);
+    read_num!(read_u16, u16);
+    read_num!(read_i16, i16);
+    read_num!(read_fixed_i32, i32);
+    read_num!(read_fixed_i64, i64);
+    read_num!(read_f32, f32);
+    read_num!(read_f64, f64);
 
-            Ok(Tag::ByteArray(v))
-        },
-        11 => {
-            let len = buf.get_i32();
-            let mut v = vec![];
+    fn read_var(&mut self, max_bytes: usize) -> Result<u64, NbtError> {
+        let mut value = 0u64;
 
-            for _ in 0..len {
-                v.push(buf.get_i32());
+        for i in 0..max_bytes {
+            let byte = self.read_u8()?;
+
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
             }
+        }
+
+        Err(self.error(DecodeError::InvalidVarInt))
+    }
+
+    // ints and longs are zigzag varints in the bedrock network dialect
+    fn read_i32(&mut self) -> Result<i32, NbtError> {
+        if self.dialect.is_varint() {
+            let value = self.read_var(5)? as u32;
+
+            Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+        } else {
+            self.read_fixed_i32()
+        }
+    }
+
+    fn read_i64(&mut self) -> Result<i64, NbtError> {
+        if self.dialect.is_varint() {
+            let value = self.read_var(10)?;
 
-            Ok(Tag::IntArray(v))
-        },
-        12 => {
-            let len = buf.get_i32();
-            let mut v = vec![];
+            Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+        } else {
+            self.read_fixed_i64()
+        }
+    }
+
+    pub(crate) fn read_string_len(&mut self) -> Result<usize, NbtError> {
+        if self.dialect.is_varint() {
+            let len = self.read_var(5)?;
 
-            for _ in 0..len {
-                v.push(buf.get_i64());
+            if len > i16::MAX as u64 {
+                return Err(self.error(DecodeError::InvalidString));
             }
 
-            Ok(Tag::LongArray(v))
-        },
+            Ok(len as usize)
+        } else {
+            Ok(self.read_u16()? as usize)
+        }
+    }
+
+    pub(crate) fn read_string(&mut self, len: usize) -> Result<String, NbtError> {
+        let mut bytes = vec![0; len];
+
+        self.read_bytes(&mut bytes)?;
 
-        _ => anyhow::bail!("unknown id: {}", id),
+        let s = if self.dialect == Dialect::Java {
+            mutf8::decode(&bytes)
+        } else {
+            String::from_utf8(bytes).ok()
+        };
+
+        s.ok_or_else(|| self.error(DecodeError::InvalidString))
     }
-}
 
-fn decode_nbt(buf: &mut Bytes) -> anyhow::Result<Nbt> {
-    let id = buf.get_u8();
+    // checks a length against the limits before anything is allocated for it
+    pub(crate) fn read_len(&mut self, element_size: u64) -> Result<usize, NbtError> {
+        let len = self.read_i32()?;
 
-    let name_len = if id != 0 {
-        buf.get_u16() as usize
-    } else {
-        0
-    };
+        if len < 0 {
+            return Err(self.error(DecodeError::NegativeLength(len)));
+        }
+
+        let len = len as usize;
+
+        if len > self.limits.max_array_len {
+            return Err(self.error(DecodeError::ArrayLimit(len, self.limits.max_array_len)));
+        }
+
+        // varint elements may be as small as a single byte
+        let element_size = if self.dialect.is_varint() { 1 } else { element_size };
+
+        if self.position + len as u64 * element_size > self.limits.max_size {
+            return Err(self.error(DecodeError::SizeLimit(self.limits.max_size)));
+        }
+
+        Ok(len)
+    }
+
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<(), NbtError> {
+        if depth >= self.limits.max_depth {
+            Err(self.error(DecodeError::DepthLimit(self.limits.max_depth)))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn decode_tag(&mut self, id: u8, depth: usize) -> Result<Tag, NbtError> {
+        match id {
+            0 => Ok(Tag::End),
+
+            1 => Ok(Tag::Byte(self.read_i8()?)),
+            2 => Ok(Tag::Short(self.read_i16()?)),
+            3 => Ok(Tag::Int(self.read_i32()?)),
+            4 => Ok(Tag::Long(self.read_i64()?)),
+
+            5 => Ok(Tag::Float(self.read_f32()?)),
+            6 => Ok(Tag::Double(self.read_f64()?)),
+
+            8 => {
+                let len = self.read_string_len()?;
+
+                Ok(Tag::String(self.read_string(len)?))
+            },
+
+            9 => {
+                self.check_depth(depth)?;
+
+                let tag_id = self.read_u8()?;
+                let len = self.read_len(1)?;
 
-    let name = if name_len > 0 {
-        let s = match str::from_utf8(&buf.bytes()[0..name_len]) {
-            Ok(s) => s.to_owned(),
-            Err(_) => anyhow::bail!("failed to decode name"),
+                // a list of end tags reads nothing per element, so only allow it empty
+                if tag_id == 0 && len > 0 {
+                    return Err(self.error(DecodeError::InvalidList));
+                }
+
+                let mut v = Vec::with_capacity(len.min(MAX_PREALLOC));
+
+                for i in 0..len {
+                    self.path.push(PathSegment::Index(i));
+                    v.push(self.decode_tag(tag_id, depth + 1)?);
+                    self.path.pop();
+                }
+
+                Ok(Tag::List(List::from_tags_unchecked(tag_id, v)))
+            },
+
+            10 => {
+                self.check_depth(depth)?;
+
+                let mut m = Map::new();
+
+                loop {
+                    let nbt = self.decode_nbt(depth + 1)?;
+
+                    if nbt.tag == Tag::End {
+                        break;
+                    }
+
+                    m.insert(nbt.name, nbt.tag);
+                }
+
+                Ok(Tag::Compound(m))
+            },
+
+            7 => {
+                let len = self.read_len(1)?;
+                let mut v = Vec::with_capacity(len.min(MAX_PREALLOC));
+
+                for _ in 0..len {
+                    v.push(self.read_i8()?);
+                }
+
+                Ok(Tag::ByteArray(v))
+            },
+            11 => {
+                let len = self.read_len(4)?;
+                let mut v = Vec::with_capacity(len.min(MAX_PREALLOC));
+
+                for _ in 0..len {
+                    v.push(self.read_i32()?);
+                }
+
+                Ok(Tag::IntArray(v))
+            },
+            12 => {
+                let len = self.read_len(8)?;
+                let mut v = Vec::with_capacity(len.min(MAX_PREALLOC));
+
+                for _ in 0..len {
+                    v.push(self.read_i64()?);
+                }
+
+                Ok(Tag::LongArray(v))
+            },
+
+            _ => Err(self.error(DecodeError::UnknownId(id))),
+        }
+    }
+
+    fn decode_nbt(&mut self, depth: usize) -> Result<Nbt, NbtError> {
+        let id = self.read_u8()?;
+
+        let name_len = if id != 0 {
+            self.read_string_len()?
+        } else {
+            0
         };
 
-        // skip name string bytes
-        buf.advance(name_len);
+        let name = if name_len > 0 {
+            self.read_string(name_len)?
+        } else {
+            String::new()
+        };
 
-        // return name
-        s
-    } else {
-        String::new()
-    };
+        // decode nbt tag, tracking its name for error paths
+        self.path.push(PathSegment::Key(name));
 
-    // decode nbt tag
-    let tag = decode_tag(buf, id)?;
+        let tag = self.decode_tag(id, depth)?;
+        let name = match self.path.pop() {
+            Some(PathSegment::Key(name)) => name,
+            _ => String::new(),
+        };
 
-    // return nbt with header
-    Ok(Nbt::new(name, tag ))
+        // return nbt with header
+        Ok(Nbt::new(name, tag))
+    }
 }
 
-impl Nbt {
-    pub fn decode<R: Read>(src: &mut R) -> anyhow::Result<Self> {
-        // decode gz compression
-        let mut buf = vec![];
+// inflates a whole buffer, for reading it again without a copy through NbtView
+pub(crate) fn decompress(data: &[u8], compression: Compression) -> Result<Vec<u8>, NbtError> {
+    let mut out = vec![];
 
-        src.read_to_end(&mut buf)?;
+    Decoder::new(data, compression)?.stream.read_to_end(&mut out)?;
 
-        // try to decompress source
-        {
-            let mut decompressed = vec![];
-            let mut gz = GzDecoder::new(&buf[..]);
+    Ok(out)
+}
 
-            // change buffer if decompression worked
-            if let Ok(_) = gz.read_to_end(&mut decompressed) {
-                buf = decompressed;
-            }
-        }
+impl Nbt {
+    pub fn decode<R: Read>(src: &mut R) -> Result<Self, NbtError> {
+        Self::decode_with(src, Compression::Auto)
+    }
 
-        let mut bytes = Bytes::copy_from_slice(&buf);
+    pub fn decode_with<R: Read>(src: &mut R, compression: Compression) -> Result<Self, NbtError> {
+        Decoder::new(src, compression)?.decode()
+    }
+
+    pub fn decode_dialect<R: Read>(
+        src: &mut R,
+        compression: Compression,
+        dialect: Dialect,
+    ) -> Result<Self, NbtError> {
+        Decoder::new(src, compression)?
+            .with_dialect(dialect)
+            .decode()
+    }
 
-        // return nbt
-        decode_nbt(&mut bytes)
+    pub fn decode_network<R: Read>(src: &mut R) -> Result<Self, NbtError> {
+        Decoder::new(src, Compression::None)?
+            .with_network(true)
+            .with_limits(Limits::network())
+            .decode()
     }
 }
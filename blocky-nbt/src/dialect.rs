@@ -0,0 +1,20 @@
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    // big-endian, as used by java edition files and protocol
+    #[default]
+    Java,
+    // little-endian, as used by bedrock edition leveldb values and level.dat
+    Bedrock,
+    // little-endian with zigzag varint ints, longs and lengths, as used by the bedrock protocol
+    BedrockNetwork,
+}
+
+impl Dialect {
+    pub fn is_little_endian(&self) -> bool {
+        *self != Self::Java
+    }
+
+    pub fn is_varint(&self) -> bool {
+        *self == Self::BedrockNetwork
+    }
+}
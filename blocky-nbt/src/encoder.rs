@@ -1,148 +1,327 @@
-use crate::{Nbt, Tag, Kind};
-use flate2::write::GzEncoder;
-use flate2::Compression;
+use crate::{Nbt, Tag, Kind, Compression, Dialect, NbtError};
+use crate::lz4::Lz4Writer;
+use crate::mutf8;
+use flate2::write::{GzEncoder, ZlibEncoder, DeflateEncoder};
 use bytes::{BytesMut, BufMut};
-use std::io::Write;
+use std::borrow::Cow;
+use std::io::{self, Write};
 
-fn encode_tag(buf: &mut BytesMut, tag: &Tag) -> anyhow::Result<()> {
-    let kind = tag.kind();
+macro_rules! put_num {
+    ($name:ident, $typ:ty, $be:ident, $le:ident) => {
+        fn $name(&mut self, value: $typ) {
+            if self.dialect.is_little_endian() {
+                self.buf.$le(value);
+            } else {
+                self.buf.$be(value);
+            }
+        }
+    };
+}
 
-    match tag {
-        Tag::End => panic!("cannot encode null"),
-
-        Tag::Byte(value) => {
-            buf.reserve(1);
-            buf.put_i8(*value);
-        },
-        Tag::Short(value) => {
-            buf.reserve(2);
-            buf.put_i16(*value);
-        },
-        Tag::Int(value) => {
-            buf.reserve(4);
-            buf.put_i32(*value);
-        },
-        Tag::Long(value) => {
-            buf.reserve(8);
-            buf.put_i64(*value);
-        },
-
-        Tag::Float(value) => {
-            buf.reserve(4);
-            buf.put_f32(*value);
-        },
-        Tag::Double(value) => {
-            buf.reserve(8);
-            buf.put_f64(*value);
-        },
-
-        Tag::String(value) => {
-            let value = value.as_bytes();
-            let len = value.len();
-
-            buf.reserve(2 + len);
-            buf.put_i16(len as i16);
-            buf.put_slice(value);
-        },
-
-        Tag::List(v) => {
-            let len = v.len() as i32;
-            let tag_id = match kind {
-                Kind::List(tag_id) => tag_id,
-                _ => anyhow::bail!("kind doesn't match tag"),
-            };
-
-            buf.reserve(5);
-            buf.put_u8(tag_id);
-            buf.put_i32(len);
-
-            for tag in v {
-                encode_tag(buf, tag)?;
+// compresses everything written to it, where auto means gzip
+pub(crate) enum Sink<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zlib(ZlibEncoder<W>),
+    Deflate(DeflateEncoder<W>),
+    Lz4(Lz4Writer<W>),
+}
+
+impl<W: Write> Sink<W> {
+    pub(crate) fn new(dst: W, compression: Compression) -> Self {
+        match compression {
+            Compression::None => Self::Plain(dst),
+            Compression::Gzip | Compression::Auto => Self::Gzip(GzEncoder::new(dst, flate2::Compression::default())),
+            Compression::Zlib => Self::Zlib(ZlibEncoder::new(dst, flate2::Compression::default())),
+            Compression::Deflate => Self::Deflate(DeflateEncoder::new(dst, flate2::Compression::default())),
+            Compression::Lz4 => Self::Lz4(Lz4Writer::new(dst)),
+        }
+    }
+
+    pub(crate) fn finish(self) -> io::Result<W> {
+        match self {
+            Self::Plain(dst) => Ok(dst),
+            Self::Gzip(gz) => gz.finish(),
+            Self::Zlib(zlib) => zlib.finish(),
+            Self::Deflate(deflate) => deflate.finish(),
+            Self::Lz4(lz4) => lz4.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for Sink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Plain(dst) => dst.write(buf),
+            Self::Gzip(gz) => gz.write(buf),
+            Self::Zlib(zlib) => zlib.write(buf),
+            Self::Deflate(deflate) => deflate.write(buf),
+            Self::Lz4(lz4) => lz4.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Plain(dst) => dst.flush(),
+            Self::Gzip(gz) => gz.flush(),
+            Self::Zlib(zlib) => zlib.flush(),
+            Self::Deflate(deflate) => deflate.flush(),
+            Self::Lz4(lz4) => lz4.flush(),
+        }
+    }
+}
+
+pub(crate) struct Encoder {
+    pub(crate) buf: BytesMut,
+    pub(crate) dialect: Dialect,
+}
+
+impl Encoder {
+    pub(crate) fn new(dialect: Dialect) -> Self {
+        Self {
+            buf: BytesMut::new(),
+            dialect,
+        }
+    }
+
+    put_num!(put_i16, i16, put_i16, put_i16_le);
+    put_num!(put_u16, u16, put_u16, put_u16_le);
+    put_num!(put_fixed_i32, i32, put_i32, put_i32_le);
+    put_num!(put_fixed_i64, i64, put_i64, put_i64_le);
+    put_num!(put_f32, f32, put_f32, put_f32_le);
+    put_num!(put_f64, f64, put_f64, put_f64_le);
+
+    fn put_var(&mut self, mut value: u64) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+
+            value >>= 7;
+
+            if value == 0 {
+                self.buf.put_u8(byte);
+                break;
             }
-        },
 
-        Tag::Compound(m) => {
-            for (name, tag)  in m {
-                if *tag != Tag::End {
-                    encode_nbt(buf, name, tag)?;
+            self.buf.put_u8(byte | 0x80);
+        }
+    }
+
+    // ints and longs are zigzag varints in the bedrock network dialect
+    pub(crate) fn put_i32(&mut self, value: i32) {
+        if self.dialect.is_varint() {
+            self.put_var(((value << 1) ^ (value >> 31)) as u32 as u64);
+        } else {
+            self.put_fixed_i32(value);
+        }
+    }
+
+    fn put_i64(&mut self, value: i64) {
+        if self.dialect.is_varint() {
+            self.put_var(((value << 1) ^ (value >> 63)) as u64);
+        } else {
+            self.put_fixed_i64(value);
+        }
+    }
+
+    pub(crate) fn put_string(&mut self, value: &str) -> Result<(), NbtError> {
+        let value = if self.dialect == Dialect::Java {
+            mutf8::encode(value)
+        } else {
+            Cow::Borrowed(value.as_bytes())
+        };
+
+        let max_len = if self.dialect.is_varint() {
+            i16::MAX as usize
+        } else {
+            u16::MAX as usize
+        };
+
+        if value.len() > max_len {
+            return Err(NbtError::StringTooLong(value.len()));
+        }
+
+        self.buf.reserve(5 + value.len());
+
+        if self.dialect.is_varint() {
+            self.put_var(value.len() as u64);
+        } else {
+            self.put_u16(value.len() as u16);
+        }
+
+        self.buf.put_slice(&value);
+
+        Ok(())
+    }
+
+    pub(crate) fn encode_tag(&mut self, tag: &Tag) -> Result<(), NbtError> {
+        match tag {
+            Tag::End => return Err(NbtError::EncodeEnd),
+
+            Tag::Byte(value) => {
+                self.buf.reserve(1);
+                self.buf.put_i8(*value);
+            },
+            Tag::Short(value) => {
+                self.buf.reserve(2);
+                self.put_i16(*value);
+            },
+            Tag::Int(value) => {
+                self.buf.reserve(5);
+                self.put_i32(*value);
+            },
+            Tag::Long(value) => {
+                self.buf.reserve(10);
+                self.put_i64(*value);
+            },
+
+            Tag::Float(value) => {
+                self.buf.reserve(4);
+                self.put_f32(*value);
+            },
+            Tag::Double(value) => {
+                self.buf.reserve(8);
+                self.put_f64(*value);
+            },
+
+            Tag::String(value) => self.put_string(value)?,
+
+            Tag::List(v) => {
+                v.validate()?;
+
+                self.buf.reserve(6);
+                self.buf.put_u8(v.id());
+                self.put_i32(v.len() as i32);
+
+                for tag in v {
+                    self.encode_tag(tag)?;
                 }
-            }
+            },
 
-            buf.reserve(1);
-            buf.put_u8(Kind::End.id());
-        },
+            Tag::Compound(m) => {
+                for (name, tag) in m {
+                    if *tag != Tag::End {
+                        self.encode_nbt(name, tag)?;
+                    }
+                }
 
-        Tag::ByteArray(v) => {
-            let len = v.len();
+                self.buf.reserve(1);
+                self.buf.put_u8(Kind::End.id());
+            },
 
-            buf.reserve(4 + len);
-            buf.put_i32(len as i32);
+            Tag::ByteArray(v) => {
+                let len = v.len();
 
-            for value in v {
-                buf.put_i8(*value);
-            }
-        },
-        Tag::IntArray(v) => {
-            let len = v.len();
+                self.buf.reserve(5 + len);
+                self.put_i32(len as i32);
 
-            buf.reserve(4 + len * 4);
-            buf.put_i32(len as i32);
+                for value in v {
+                    self.buf.put_i8(*value);
+                }
+            },
+            Tag::IntArray(v) => {
+                let len = v.len();
 
-            for value in v {
-                buf.put_i32(*value);
-            }
-        },
-        Tag::LongArray(v) => {
-            let len = v.len();
+                self.buf.reserve(5 + len * 4);
+                self.put_i32(len as i32);
 
-            buf.reserve(4 + len * 8);
-            buf.put_i32(len as i32);
+                for value in v {
+                    self.put_i32(*value);
+                }
+            },
+            Tag::LongArray(v) => {
+                let len = v.len();
 
-            for value in v {
-                buf.put_i64(*value);
-            }
-        },
+                self.buf.reserve(5 + len * 8);
+                self.put_i32(len as i32);
+
+                for value in v {
+                    self.put_i64(*value);
+                }
+            },
+        }
+
+        Ok(())
     }
 
-    Ok(())
+    fn encode_nbt(&mut self, name: &str, tag: &Tag) -> Result<(), NbtError> {
+        // add nbt id
+        self.buf.reserve(1);
+        self.buf.put_u8(tag.kind().id());
+
+        // add name length and contents
+        self.put_string(name)?;
+
+        self.encode_tag(tag)
+    }
 }
 
-fn encode_nbt(buf: &mut BytesMut, name: &str, tag: &Tag) -> anyhow::Result<()> {
-    let id = tag.kind().id();
-    let name = name.as_bytes();
-    let name_len = name.len();
+impl Tag {
+    // writes the nameless root used by the protocol since 1.20.2, an end tag meaning no data
+    pub fn encode_network<W: Write>(&self, dst: &mut W) -> Result<(), NbtError> {
+        let mut encoder = Encoder::new(Dialect::Java);
 
-    // reserve id and name
-    buf.reserve(1 + name_len * 2);
+        encoder.buf.put_u8(self.kind().id());
 
-    // add nbt id
-    buf.put_u8(id);
+        if *self != Tag::End {
+            encoder.encode_tag(self)?;
+        }
 
-    // add name length and contents
-    buf.put_u16(name_len as u16);
-    buf.put_slice(name);
+        dst.write_all(&encoder.buf)?;
+
+        Ok(())
+    }
 
-    encode_tag(buf, &tag)?;
+    // the number of bytes encode_network writes, counted without encoding. tags it would fail
+    // on, like mixed lists or overlong strings, still get a length, since the error is left
+    // for encoding to report
+    pub fn network_len(&self) -> usize {
+        1 + payload_len(self)
+    }
+}
 
-    Ok(())
+fn payload_len(tag: &Tag) -> usize {
+    match tag {
+        Tag::End => 0,
+        Tag::Byte(_) => 1,
+        Tag::Short(_) => 2,
+        Tag::Int(_) | Tag::Float(_) => 4,
+        Tag::Long(_) | Tag::Double(_) => 8,
+        Tag::String(value) => 2 + mutf8::encode(value).len(),
+        Tag::List(v) => 5 + v.iter().map(payload_len).sum::<usize>(),
+        Tag::Compound(m) => 1 + m.iter()
+            .filter(|(_, tag)| **tag != Tag::End)
+            .map(|(name, tag)| 3 + mutf8::encode(name).len() + payload_len(tag))
+            .sum::<usize>(),
+        Tag::ByteArray(v) => 4 + v.len(),
+        Tag::IntArray(v) => 4 + v.len() * 4,
+        Tag::LongArray(v) => 4 + v.len() * 8,
+    }
 }
 
 impl Nbt {
-    pub fn encode<W: Write>(&self, dst: &mut W, compress: bool) -> anyhow::Result<()> {
-        let mut bytes = BytesMut::new();
+    pub fn encode_network<W: Write>(&self, dst: &mut W) -> Result<(), NbtError> {
+        self.tag.encode_network(dst)
+    }
+
+    pub fn encode<W: Write>(&self, dst: &mut W, compression: Compression) -> Result<(), NbtError> {
+        self.encode_dialect(dst, compression, Dialect::Java)
+    }
+
+    pub fn encode_dialect<W: Write>(
+        &self,
+        dst: &mut W,
+        compression: Compression,
+        dialect: Dialect,
+    ) -> Result<(), NbtError> {
+        let mut encoder = Encoder::new(dialect);
 
         // encode nbts into uncompressed bytes
-        encode_nbt(&mut bytes, &self.name, &self.tag)?;
+        encoder.encode_nbt(&self.name, &self.tag)?;
 
-        if compress {
-            // create encoder
-            let mut gz = GzEncoder::new(dst, Compression::default());
+        let mut sink = Sink::new(dst, compression);
 
-            // compress bytes
-            gz.write_all(&bytes)?;
-        } else {
-            dst.write_all(&bytes)?;
-        }
+        sink.write_all(&encoder.buf)?;
+        sink.finish()?;
 
         Ok(())
     }
@@ -0,0 +1,166 @@
+use crate::{Compression, Kind, PathSegment};
+use crate::tag::path_to_string;
+use std::fmt;
+use std::io;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum NbtError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("{kind} at byte {offset} (path: {path})")]
+    Decode {
+        kind: DecodeError,
+        offset: u64,
+        path: String,
+    },
+    #[error(transparent)]
+    Syntax(#[from] ParseError),
+    #[error("{0}")]
+    Message(String),
+    #[error("unknown chunk compression type: {0}")]
+    UnknownCompression(u8),
+    #[error("compression cannot be stored in a region file: {0:?}")]
+    UnsupportedCompression(Compression),
+    #[error("corrupt region data for chunk {0}, {1}")]
+    CorruptChunk(i32, i32),
+    #[error("cannot encode end tag")]
+    EncodeEnd,
+    #[error("string of {0} bytes is too long to encode")]
+    StringTooLong(usize),
+    #[error("cannot serialize type: {0}")]
+    UnsupportedType(&'static str),
+    #[error("compound keys must be strings")]
+    KeyMustBeString,
+    #[error("list has mixed tag types")]
+    MixedList,
+    #[error("expected {0} array elements")]
+    InvalidArray(&'static str),
+    #[error("expected {expected} but found {found:?}")]
+    WrongType {
+        expected: &'static str,
+        found: Kind,
+    },
+    #[error("cannot index {0:?} tag")]
+    InvalidIndex(Kind),
+    #[error("patch does not apply at path: {0}")]
+    PatchMismatch(String),
+    #[error("missing field: {0}")]
+    MissingField(&'static str),
+    #[error("invalid resource location: {0}")]
+    InvalidResourceLocation(String),
+    #[error("invalid block state: {0}")]
+    InvalidBlockState(String),
+    #[error("no tag at the fixer's root path")]
+    MissingRoot,
+    #[error("fix {name:?} for data version {version} failed: {source}")]
+    FixFailed {
+        version: i32,
+        name: String,
+        source: Box<NbtError>,
+    },
+    #[error("expected {expected} longs of packed data but found {found}")]
+    PackedLength {
+        expected: usize,
+        found: usize,
+    },
+    #[error("palette index {0} is out of range for {1} entries")]
+    InvalidPalette(i64, usize),
+    #[error("y {0} is outside the sections a chunk can hold")]
+    HeightOutOfRange(i32),
+    #[error("unknown game type: {0}")]
+    UnknownGameType(i32),
+    #[error("unexpected event: {0}")]
+    UnexpectedEvent(String),
+    #[error("events ended before the root tag was closed")]
+    UnfinishedEvents,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum DecodeError {
+    #[error("unexpected end of data")]
+    UnexpectedEof,
+    #[error("unknown id: {0}")]
+    UnknownId(u8),
+    #[error("failed to decode string")]
+    InvalidString,
+    #[error("list of end tags must be empty")]
+    InvalidList,
+    #[error("varint is too long")]
+    InvalidVarInt,
+    #[error("negative length: {0}")]
+    NegativeLength(i32),
+    #[error("nesting depth exceeds limit of {0}")]
+    DepthLimit(usize),
+    #[error("data size exceeds limit of {0} bytes")]
+    SizeLimit(u64),
+    #[error("array length {0} exceeds limit of {1}")]
+    ArrayLimit(usize, usize),
+}
+
+// an snbt syntax error, with offsets in chars and one-based lines and columns
+#[derive(Error, Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub expected: Vec<String>,
+    // the offending line with a caret under the error column
+    pub snippet: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)?;
+
+        let expected: Vec<_> = self.expected.iter().map(|token| format!("`{}`", token)).collect();
+
+        match expected.len() {
+            0 => {},
+            1 => write!(f, " (expected {})", expected[0])?,
+            _ => write!(f, " (expected one of {})", expected.join(", "))?,
+        }
+
+        write!(f, "\n{}", self.snippet)
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum SchemaViolation {
+    #[error("expected {expected} but found {found:?}")]
+    WrongType {
+        expected: &'static str,
+        found: Kind,
+    },
+    #[error("missing required field")]
+    Missing,
+    #[error("unknown field")]
+    Unknown,
+    #[error("expected 0b or 1b but found {0}b")]
+    NotBool(i8),
+    #[error("invalid resource location {0:?}: {1}")]
+    InvalidResourceLocation(String, String),
+    #[error("matches none of the allowed schemas")]
+    NoMatch,
+}
+
+// one place a tag doesn't match a schema
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{}: {violation}", if path.is_empty() { String::from("(root)") } else { path_to_string(path) })]
+pub struct SchemaError {
+    pub path: Vec<PathSegment>,
+    pub violation: SchemaViolation,
+}
+
+impl serde::ser::Error for NbtError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
+
+impl serde::de::Error for NbtError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self::Message(msg.to_string())
+    }
+}
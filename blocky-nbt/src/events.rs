@@ -0,0 +1,404 @@
+use crate::{Tag, Kind, Compression, Dialect, NbtError, DecodeError, PathSegment};
+use crate::decoder::Decoder;
+use crate::encoder::{Encoder, Sink};
+use bytes::BufMut;
+use std::io::{Read, Write};
+
+// buffered output is handed to the writer once it grows past this
+const FLUSH_SIZE: usize = 8192;
+
+// one step through a tag tree, in the order the tags are encoded. compounds are opened with
+// their name, empty inside lists, and other compound entries are announced with a field before
+// their value. lists and compounds are both closed by end
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartCompound(String),
+    Field(String, Kind),
+    Primitive(Tag),
+    // the kind of the elements, where a list of lists can't say what the inner lists hold
+    // and uses Kind::List(0)
+    StartList(Kind, usize),
+    End,
+}
+
+fn kind_of(id: u8, element: u8) -> Option<Kind> {
+    Some(match id {
+        0 => Kind::End,
+        1 => Kind::Byte,
+        2 => Kind::Short,
+        3 => Kind::Int,
+        4 => Kind::Long,
+        5 => Kind::Float,
+        6 => Kind::Double,
+        7 => Kind::ByteArray,
+        8 => Kind::String,
+        9 => Kind::List(element),
+        10 => Kind::Compound,
+        11 => Kind::IntArray,
+        12 => Kind::LongArray,
+        _ => return None,
+    })
+}
+
+enum Frame {
+    Compound,
+    List {
+        id: u8,
+        len: usize,
+        index: usize,
+    },
+}
+
+// a pull parser over the same wire format as Decoder, holding only the open containers
+pub struct EventReader<R> {
+    decoder: Decoder<R>,
+    stack: Vec<Frame>,
+    // a value announced by a field, with the element id already read for lists
+    pending: Option<(u8, u8)>,
+    started: bool,
+    done: bool,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn events(self) -> EventReader<R> {
+        EventReader {
+            decoder: self,
+            stack: vec![],
+            pending: None,
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> EventReader<R> {
+    // other settings are made on a Decoder before calling events
+    pub fn new(src: R, compression: Compression) -> Result<Self, NbtError> {
+        Ok(Decoder::new(src, compression)?.events())
+    }
+
+    // how many compounds and lists are open
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn next_event(&mut self) -> Result<Option<Event>, NbtError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let event = if !self.started {
+            self.started = true;
+            self.read_root()?
+        } else if let Some((id, element)) = self.pending.take() {
+            self.read_value(id, Some(element))?
+        } else {
+            match self.stack.last_mut() {
+                Some(Frame::Compound) => self.read_entry()?,
+                Some(Frame::List { len, index, .. }) if *index == *len => self.close(),
+                Some(Frame::List { id, index, .. }) => {
+                    let id = *id;
+
+                    self.decoder.path.push(PathSegment::Index(*index));
+                    *index += 1;
+
+                    self.read_value(id, None)?
+                },
+                None => None,
+            }
+        };
+
+        if self.stack.is_empty() && self.pending.is_none() {
+            self.done = true;
+        }
+
+        Ok(event)
+    }
+
+    // skips a value announced by a field, or the rest of the innermost open container
+    pub fn skip_subtree(&mut self) -> Result<(), NbtError> {
+        let depth = if self.pending.is_some() {
+            let depth = self.stack.len() + 1;
+
+            self.next_event()?;
+            depth
+        } else {
+            self.stack.len()
+        };
+
+        while self.stack.len() >= depth && !self.done {
+            self.next_event()?;
+        }
+
+        Ok(())
+    }
+
+    fn kind(&self, id: u8, element: u8) -> Result<Kind, NbtError> {
+        kind_of(id, element).ok_or_else(|| self.decoder.error(DecodeError::UnknownId(id)))
+    }
+
+    fn read_root(&mut self) -> Result<Option<Event>, NbtError> {
+        self.decoder.position = 0;
+        self.decoder.path.clear();
+
+        let id = self.decoder.read_u8()?;
+
+        // an end tag as the root means there is no data
+        if id == 0 {
+            return Ok(None);
+        }
+
+        let name = if self.decoder.network {
+            String::new()
+        } else {
+            let len = self.decoder.read_string_len()?;
+
+            self.decoder.read_string(len)?
+        };
+
+        self.read_named(id, name).map(Some)
+    }
+
+    fn read_entry(&mut self) -> Result<Option<Event>, NbtError> {
+        let id = self.decoder.read_u8()?;
+
+        if id == 0 {
+            return Ok(self.close());
+        }
+
+        let len = self.decoder.read_string_len()?;
+        let name = self.decoder.read_string(len)?;
+
+        self.read_named(id, name).map(Some)
+    }
+
+    fn read_named(&mut self, id: u8, name: String) -> Result<Event, NbtError> {
+        self.decoder.path.push(PathSegment::Key(name.clone()));
+
+        match id {
+            10 => {
+                self.open(Frame::Compound)?;
+
+                Ok(Event::StartCompound(name))
+            },
+            9 => {
+                let element = self.decoder.read_u8()?;
+                let kind = self.kind(id, element)?;
+
+                self.pending = Some((id, element));
+
+                Ok(Event::Field(name, kind))
+            },
+            _ => {
+                let kind = self.kind(id, 0)?;
+
+                self.pending = Some((id, 0));
+
+                Ok(Event::Field(name, kind))
+            },
+        }
+    }
+
+    fn read_value(&mut self, id: u8, element: Option<u8>) -> Result<Option<Event>, NbtError> {
+        match id {
+            10 => {
+                self.open(Frame::Compound)?;
+
+                Ok(Some(Event::StartCompound(String::new())))
+            },
+            9 => {
+                let element = match element {
+                    Some(element) => element,
+                    None => self.decoder.read_u8()?,
+                };
+
+                let len = self.decoder.read_len(1)?;
+
+                // a list of end tags reads nothing per element, so only allow it empty
+                if element == 0 && len > 0 {
+                    return Err(self.decoder.error(DecodeError::InvalidList));
+                }
+
+                let kind = self.kind(element, 0)?;
+
+                self.open(Frame::List { id: element, len, index: 0 })?;
+
+                Ok(Some(Event::StartList(kind, len)))
+            },
+            _ => {
+                let tag = self.decoder.decode_tag(id, self.stack.len())?;
+
+                self.decoder.path.pop();
+
+                Ok(Some(Event::Primitive(tag)))
+            },
+        }
+    }
+
+    fn open(&mut self, frame: Frame) -> Result<(), NbtError> {
+        self.decoder.check_depth(self.stack.len())?;
+        self.stack.push(frame);
+
+        Ok(())
+    }
+
+    fn close(&mut self) -> Option<Event> {
+        self.stack.pop();
+        self.decoder.path.pop();
+
+        Some(Event::End)
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<Event, NbtError>;
+
+    // stops after the first error
+    fn next(&mut self) -> Option<Self::Item> {
+        let event = self.next_event();
+
+        if event.is_err() {
+            self.done = true;
+        }
+
+        event.transpose()
+    }
+}
+
+enum Slot {
+    List {
+        id: u8,
+        remaining: usize,
+    },
+    Compound,
+}
+
+// the push counterpart of EventReader, writing through the same encoder as Nbt::encode and
+// checking that the events make a complete tag
+pub struct EventWriter<W: Write> {
+    encoder: Encoder,
+    sink: Sink<W>,
+    network: bool,
+    stack: Vec<Slot>,
+    // the id of a value announced by a field
+    pending: Option<u8>,
+    started: bool,
+}
+
+impl<W: Write> EventWriter<W> {
+    pub fn new(dst: W, compression: Compression) -> Self {
+        Self {
+            encoder: Encoder::new(Dialect::Java),
+            sink: Sink::new(dst, compression),
+            network: false,
+            stack: vec![],
+            pending: None,
+            started: false,
+        }
+    }
+
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.encoder.dialect = dialect;
+        self
+    }
+
+    // the network variant used by the protocol since 1.20.2 has no root name
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub fn write(&mut self, event: &Event) -> Result<(), NbtError> {
+        let unexpected = || NbtError::UnexpectedEvent(format!("{:?}", event));
+
+        // the id a value written now must have, or none where only names and ends fit
+        let value = match (self.pending, self.stack.last()) {
+            (Some(id), _) => Some(id),
+            (None, Some(Slot::List { remaining: 0, .. })) => None,
+            (None, Some(Slot::List { id, .. })) => Some(*id),
+            (None, Some(Slot::Compound)) => None,
+            (None, None) if !self.started => None,
+            (None, None) => return Err(unexpected()),
+        };
+
+        match (value, event) {
+            (None, Event::StartCompound(name)) if !matches!(self.stack.last(), Some(Slot::List { .. })) => {
+                self.write_name(10, name)?;
+                self.stack.push(Slot::Compound);
+            },
+            (None, Event::Field(name, kind)) if !matches!(self.stack.last(), Some(Slot::List { .. })) && *kind != Kind::End => {
+                self.write_name(kind.id(), name)?;
+                self.pending = Some(kind.id());
+            },
+            (None, Event::End) if !self.stack.is_empty() => {
+                if let Some(Slot::Compound) = self.stack.pop() {
+                    self.encoder.buf.put_u8(Kind::End.id());
+                }
+            },
+            (Some(10), Event::StartCompound(_)) => {
+                self.take_value();
+                self.stack.push(Slot::Compound);
+            },
+            (Some(9), Event::StartList(kind, len)) => {
+                if *kind == Kind::End && *len > 0 {
+                    return Err(unexpected());
+                }
+
+                self.take_value();
+                self.encoder.buf.put_u8(kind.id());
+                self.encoder.put_i32(*len as i32);
+                self.stack.push(Slot::List { id: kind.id(), remaining: *len });
+            },
+            (Some(id), Event::Primitive(tag)) if tag.kind().id() == id => {
+                self.take_value();
+                self.encoder.encode_tag(tag)?;
+            },
+            _ => return Err(unexpected()),
+        }
+
+        if self.encoder.buf.len() >= FLUSH_SIZE {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+
+    // fails unless the root tag has been closed
+    pub fn finish(mut self) -> Result<W, NbtError> {
+        if !self.started || !self.stack.is_empty() || self.pending.is_some() {
+            return Err(NbtError::UnfinishedEvents);
+        }
+
+        self.flush()?;
+
+        Ok(self.sink.finish()?)
+    }
+
+    fn write_name(&mut self, id: u8, name: &str) -> Result<(), NbtError> {
+        self.encoder.buf.put_u8(id);
+
+        // the root has no name in the network variant
+        if self.started || !self.network {
+            self.encoder.put_string(name)?;
+        }
+
+        self.started = true;
+
+        Ok(())
+    }
+
+    fn take_value(&mut self) {
+        if self.pending.take().is_none() {
+            if let Some(Slot::List { remaining, .. }) = self.stack.last_mut() {
+                *remaining -= 1;
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), NbtError> {
+        self.sink.write_all(&self.encoder.buf)?;
+        self.encoder.buf.clear();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{Nbt, Tag, Map, NbtPath, NbtError, BlockState, WORLD_GEN_SETTINGS_VERSION};
+use crate::__private::take;
+use std::convert::TryFrom;
+
+// the version of the flattening in 1.13, which replaced numeric ids and data values with
+// names and properties
+pub const FLATTENING_VERSION: i32 = 1451;
+
+// 1.16 replaced UUIDMost and UUIDLeast with a single int array
+pub const UUID_INT_ARRAY_VERSION: i32 = 2514;
+
+// 23w46a renamed grass to short_grass
+pub const SHORT_GRASS_VERSION: i32 = 3692;
+
+type FixFn = Box<dyn Fn(&mut Tag) -> Result<(), NbtError>>;
+
+struct Fix {
+    version: i32,
+    name: String,
+    apply: FixFn,
+}
+
+// upgrades data saved by older versions one fix at a time, like vanilla's DataFixerUpper.
+// each fix belongs to the data version that introduced the change, and upgrading from one
+// version to another runs every fix after the first up to and including the second, oldest
+// first and in the order they were added within a version
+//
+// a fixer covers one kind of data, since the same change is found in different places in
+// chunks, structures, player files and so on
+#[derive(Default)]
+pub struct DataFixer {
+    fixes: Vec<Fix>,
+    root: Option<NbtPath>,
+}
+
+impl DataFixer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fix<S, F>(mut self, version: i32, name: S, fix: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(&mut Tag) -> Result<(), NbtError> + 'static,
+    {
+        let index = self.fixes.iter().position(|fix| fix.version > version).unwrap_or(self.fixes.len());
+
+        self.fixes.insert(index, Fix {
+            version,
+            name: name.into(),
+            apply: Box::new(fix),
+        });
+
+        self
+    }
+
+    // the compound holding DataVersion and the data the fixes run on, where that isn't the root
+    // of the file, like Data in level.dat
+    pub fn with_root(mut self, root: NbtPath) -> Self {
+        self.root = Some(root);
+        self
+    }
+
+    // the names and versions of the fixes an upgrade would run
+    pub fn fixes(&self, from: i32, to: i32) -> impl Iterator<Item = (i32, &str)> {
+        self.fixes.iter()
+            .filter(move |fix| fix.version > from && fix.version <= to)
+            .map(|fix| (fix.version, fix.name.as_str()))
+    }
+
+    pub fn upgrade_tag(&self, tag: &mut Tag, from: i32, to: i32) -> Result<(), NbtError> {
+        for fix in self.fixes.iter().filter(|fix| fix.version > from && fix.version <= to) {
+            (fix.apply)(tag).map_err(|e| NbtError::FixFailed {
+                version: fix.version,
+                name: fix.name.clone(),
+                source: Box::new(e),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // reads the version from DataVersion in the fixer's root, which data from before 1.9 doesn't
+    // have, and sets it to the new version afterwards. returns the version the data was at
+    pub fn upgrade(&self, nbt: &mut Nbt, to: i32) -> Result<i32, NbtError> {
+        let tag = match &self.root {
+            Some(root) => root.get_all_mut(&mut nbt.tag).into_iter().next().ok_or(NbtError::MissingRoot)?,
+            None => &mut nbt.tag,
+        };
+
+        let from = match tag.get("DataVersion") {
+            Some(version) => i32::try_from(version)?,
+            None => 0,
+        };
+
+        if from < to {
+            self.upgrade_tag(tag, from, to)?;
+            tag.try_insert("DataVersion", Tag::Int(to))?;
+        }
+
+        Ok(from)
+    }
+
+    // fixes for level.dat, run on its Data compound
+    pub fn level() -> Self {
+        let player = NbtPath::parse("Player").unwrap();
+
+        Self::new()
+            .with_root(NbtPath::parse("Data").unwrap())
+            .with_fix(UUID_INT_ARRAY_VERSION, "player uuid", at(player, uuid_to_int_array("UUIDMost", "UUIDLeast", "UUID")))
+            .with_fix(WORLD_GEN_SETTINGS_VERSION, "world gen settings", world_gen_settings)
+    }
+
+    // fixes for structure block files
+    pub fn structure() -> Self {
+        let states = NbtPath::parse("palette[]").unwrap();
+        let palettes = NbtPath::parse("palettes[][]").unwrap();
+        let entities = NbtPath::parse("entities[].nbt").unwrap();
+
+        Self::new()
+            .with_fix(FLATTENING_VERSION, "flatten stone", at(states.clone(), flatten_block_states(stone_variants())))
+            .with_fix(FLATTENING_VERSION, "flatten stone in palettes", at(palettes.clone(), flatten_block_states(stone_variants())))
+            .with_fix(UUID_INT_ARRAY_VERSION, "entity uuid", at(entities, uuid_to_int_array("UUIDMost", "UUIDLeast", "UUID")))
+            .with_fix(SHORT_GRASS_VERSION, "short grass", at(states, rename_value("Name", SHORT_GRASS)))
+            .with_fix(SHORT_GRASS_VERSION, "short grass in palettes", at(palettes, rename_value("Name", SHORT_GRASS)))
+    }
+}
+
+const SHORT_GRASS: &[(&str, &str)] = &[("minecraft:grass", "minecraft:short_grass")];
+
+// a small part of the flattening table, as an example of its shape
+fn stone_variants() -> Vec<(BlockState, BlockState)> {
+    [
+        ("minecraft:stone[variant=stone]", "minecraft:stone"),
+        ("minecraft:stone[variant=granite]", "minecraft:granite"),
+        ("minecraft:stone[variant=smooth_granite]", "minecraft:polished_granite"),
+        ("minecraft:stone[variant=diorite]", "minecraft:diorite"),
+        ("minecraft:stone[variant=smooth_diorite]", "minecraft:polished_diorite"),
+        ("minecraft:stone[variant=andesite]", "minecraft:andesite"),
+        ("minecraft:stone[variant=smooth_andesite]", "minecraft:polished_andesite"),
+    ]
+    .iter()
+    .map(|(old, new)| (old.parse().unwrap(), new.parse().unwrap()))
+    .collect()
+}
+
+// runs a fix on every tag the path matches, doing nothing if there are none
+pub fn at<F>(path: NbtPath, fix: F) -> impl Fn(&mut Tag) -> Result<(), NbtError>
+where
+    F: Fn(&mut Tag) -> Result<(), NbtError>,
+{
+    move |tag| path.get_all_mut(tag).into_iter().try_for_each(&fix)
+}
+
+// moves a compound entry to a new key, replacing anything already there
+pub fn rename_key(from: &'static str, to: &'static str) -> impl Fn(&mut Tag) -> Result<(), NbtError> {
+    move |tag| {
+        if let Some(m) = tag.as_compound_mut() {
+            if let Some(value) = take(m, from) {
+                m.insert(to.to_owned(), value);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// swaps a string entry for another, like an id that changed
+pub fn rename_value(key: &'static str, renames: &'static [(&'static str, &'static str)]) -> impl Fn(&mut Tag) -> Result<(), NbtError> {
+    move |tag| {
+        if let Some(Tag::String(value)) = tag.get_mut(key) {
+            if let Some((_, new)) = renames.iter().find(|(old, _)| old == value) {
+                *value = (*new).to_owned();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// replaces block states in the Name and Properties form, matching on the whole state
+pub fn flatten_block_states(table: Vec<(BlockState, BlockState)>) -> impl Fn(&mut Tag) -> Result<(), NbtError> {
+    move |tag| {
+        let state = BlockState::from_tag(tag)?;
+
+        if let Some((_, new)) = table.iter().find(|(old, _)| *old == state) {
+            *tag = new.to_tag();
+        }
+
+        Ok(())
+    }
+}
+
+// joins the two halves of a uuid into four ints, most significant first
+pub fn uuid_to_int_array(most: &'static str, least: &'static str, to: &'static str) -> impl Fn(&mut Tag) -> Result<(), NbtError> {
+    move |tag| {
+        let m = match tag.as_compound_mut() {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+
+        if !m.contains_key(most) || !m.contains_key(least) {
+            return Ok(());
+        }
+
+        let high = i64::try_from(take(m, most).unwrap())?;
+        let low = i64::try_from(take(m, least).unwrap())?;
+        let uuid = vec![(high >> 32) as i32, high as i32, (low >> 32) as i32, low as i32];
+
+        m.insert(to.to_owned(), Tag::IntArray(uuid));
+
+        Ok(())
+    }
+}
+
+// moves the seed and structure setting into WorldGenSettings. the generator itself stays where
+// it is, since converting generatorName and generatorOptions into dimensions needs the
+// dimension types of the target version
+fn world_gen_settings(tag: &mut Tag) -> Result<(), NbtError> {
+    let m = match tag.as_compound_mut() {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+
+    if m.contains_key("WorldGenSettings") {
+        return Ok(());
+    }
+
+    let mut settings = Map::new();
+
+    settings.insert("seed".to_owned(), take(m, "RandomSeed").unwrap_or(Tag::Long(0)));
+    settings.insert("generate_features".to_owned(), take(m, "MapFeatures").unwrap_or(Tag::Byte(1)));
+    settings.insert("bonus_chest".to_owned(), Tag::Byte(0));
+
+    m.insert("WorldGenSettings".to_owned(), Tag::Compound(settings));
+
+    Ok(())
+}
@@ -0,0 +1,329 @@
+use crate::{Nbt, Tag, Map, Compression, NbtError};
+use crate::__private::{take, get};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+// the first data version to keep the seed and generator in WorldGenSettings instead of
+// RandomSeed, MapFeatures and generatorName
+pub const WORLD_GEN_SETTINGS_VERSION: i32 = 2550;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameType {
+    #[default]
+    Survival,
+    Creative,
+    Adventure,
+    Spectator,
+}
+
+impl GameType {
+    pub fn id(self) -> i32 {
+        match self {
+            Self::Survival => 0,
+            Self::Creative => 1,
+            Self::Adventure => 2,
+            Self::Spectator => 3,
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Some(match id {
+            0 => Self::Survival,
+            1 => Self::Creative,
+            2 => Self::Adventure,
+            3 => Self::Spectator,
+            _ => return None,
+        })
+    }
+}
+
+// the game that last saved the world, which files from before 1.9 don't have
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GameVersion {
+    pub id: i32,
+    pub name: String,
+    pub snapshot: bool,
+    // main or a name for experimental builds, since 1.18
+    pub series: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Spawn {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+    // since 1.16
+    pub angle: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Weather {
+    pub raining: bool,
+    pub rain_time: i32,
+    pub thundering: bool,
+    pub thunder_time: i32,
+    pub clear_weather_time: i32,
+}
+
+// the same fields whichever layout the file uses. only the seed and features are written in
+// the old layout, where the rest has nowhere to go
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldGenSettings {
+    pub seed: i64,
+    pub generate_features: bool,
+    pub bonus_chest: bool,
+    pub dimensions: Option<Map<String, Tag>>,
+    // other keys of WorldGenSettings, like legacy_custom_options
+    pub extra: Map<String, Tag>,
+}
+
+impl Default for WorldGenSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            generate_features: true,
+            bonus_chest: false,
+            dimensions: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+// vanilla stores every rule as a string, whatever its type
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GameRules {
+    rules: Map<String, String>,
+}
+
+impl GameRules {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.rules.get(name).map(String::as_str)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn get_i32(&self, name: &str) -> Option<i32> {
+        self.get(name)?.parse().ok()
+    }
+
+    pub fn set<S: Into<String>, V: ToString>(&mut self, name: S, value: V) {
+        self.rules.insert(name.into(), value.to_string());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.rules.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+// the Data compound of a level.dat. where a field has moved between versions it's read from
+// either place and written where data_version says it belongs
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LevelData {
+    pub data_version: Option<i32>,
+    pub level_name: String,
+    pub version: Option<GameVersion>,
+    pub game_type: GameType,
+    pub hardcore: bool,
+    pub allow_commands: bool,
+    pub time: i64,
+    pub day_time: i64,
+    pub spawn: Spawn,
+    pub weather: Weather,
+    pub game_rules: GameRules,
+    pub world_gen: WorldGenSettings,
+    // set when WorldGenSettings was read although data_version doesn't call for it, like in a
+    // file without DataVersion, so it's written back the same way instead of losing settings
+    pub keep_world_gen_settings: bool,
+    // keys this model doesn't know about, written back unchanged
+    pub extra: Map<String, Tag>,
+}
+
+impl GameVersion {
+    fn from_tag(tag: Tag) -> Result<Self, NbtError> {
+        let mut m = Map::try_from(tag)?;
+
+        Ok(Self {
+            id: get(&mut m, "Id")?.unwrap_or_default(),
+            name: get(&mut m, "Name")?.unwrap_or_default(),
+            snapshot: get(&mut m, "Snapshot")?.unwrap_or_default(),
+            series: get(&mut m, "Series")?,
+        })
+    }
+
+    fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("Id".to_owned(), Tag::from(self.id));
+        m.insert("Name".to_owned(), Tag::from(self.name.clone()));
+        m.insert("Snapshot".to_owned(), Tag::from(self.snapshot));
+
+        if let Some(series) = &self.series {
+            m.insert("Series".to_owned(), Tag::from(series.clone()));
+        }
+
+        Tag::Compound(m)
+    }
+}
+
+impl LevelData {
+    // whether world generation is written to WorldGenSettings
+    pub fn has_world_gen_settings(&self) -> bool {
+        self.keep_world_gen_settings || self.data_version.is_some_and(|version| version >= WORLD_GEN_SETTINGS_VERSION)
+    }
+
+    pub fn from_tag(tag: Tag) -> Result<Self, NbtError> {
+        let mut m = Map::try_from(tag)?;
+
+        let game_type = get(&mut m, "GameType")?.unwrap_or_default();
+        let game_type = GameType::from_id(game_type).ok_or(NbtError::UnknownGameType(game_type))?;
+
+        let mut game_rules = GameRules::default();
+
+        if let Some(rules) = get::<Map<String, Tag>>(&mut m, "GameRules")? {
+            for (name, value) in rules {
+                game_rules.rules.insert(name, String::try_from(value)?);
+            }
+        }
+
+        let data_version = get(&mut m, "DataVersion")?;
+        let keep_world_gen_settings = m.contains_key("WorldGenSettings")
+            && data_version.is_none_or(|version| version < WORLD_GEN_SETTINGS_VERSION);
+
+        let world_gen = match get::<Map<String, Tag>>(&mut m, "WorldGenSettings")? {
+            Some(mut settings) => WorldGenSettings {
+                seed: get(&mut settings, "seed")?.unwrap_or_default(),
+                generate_features: get(&mut settings, "generate_features")?.unwrap_or(true),
+                bonus_chest: get(&mut settings, "bonus_chest")?.unwrap_or_default(),
+                dimensions: get(&mut settings, "dimensions")?,
+                extra: settings,
+            },
+            None => WorldGenSettings {
+                seed: get(&mut m, "RandomSeed")?.unwrap_or_default(),
+                generate_features: get(&mut m, "MapFeatures")?.unwrap_or(true),
+                ..WorldGenSettings::default()
+            },
+        };
+
+        Ok(Self {
+            data_version,
+            level_name: get(&mut m, "LevelName")?.unwrap_or_default(),
+            version: take(&mut m, "Version").map(GameVersion::from_tag).transpose()?,
+            game_type,
+            hardcore: get(&mut m, "hardcore")?.unwrap_or_default(),
+            allow_commands: get(&mut m, "allowCommands")?.unwrap_or_default(),
+            time: get(&mut m, "Time")?.unwrap_or_default(),
+            day_time: get(&mut m, "DayTime")?.unwrap_or_default(),
+            spawn: Spawn {
+                x: get(&mut m, "SpawnX")?.unwrap_or_default(),
+                y: get(&mut m, "SpawnY")?.unwrap_or_default(),
+                z: get(&mut m, "SpawnZ")?.unwrap_or_default(),
+                angle: get(&mut m, "SpawnAngle")?,
+            },
+            weather: Weather {
+                raining: get(&mut m, "raining")?.unwrap_or_default(),
+                rain_time: get(&mut m, "rainTime")?.unwrap_or_default(),
+                thundering: get(&mut m, "thundering")?.unwrap_or_default(),
+                thunder_time: get(&mut m, "thunderTime")?.unwrap_or_default(),
+                clear_weather_time: get(&mut m, "clearWeatherTime")?.unwrap_or_default(),
+            },
+            game_rules,
+            world_gen,
+            keep_world_gen_settings,
+            extra: m,
+        })
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        if let Some(version) = self.data_version {
+            m.insert("DataVersion".to_owned(), Tag::from(version));
+        }
+
+        m.insert("LevelName".to_owned(), Tag::from(self.level_name.clone()));
+
+        if let Some(version) = &self.version {
+            m.insert("Version".to_owned(), version.to_tag());
+        }
+
+        m.insert("GameType".to_owned(), Tag::from(self.game_type.id()));
+        m.insert("hardcore".to_owned(), Tag::from(self.hardcore));
+        m.insert("allowCommands".to_owned(), Tag::from(self.allow_commands));
+        m.insert("Time".to_owned(), Tag::from(self.time));
+        m.insert("DayTime".to_owned(), Tag::from(self.day_time));
+
+        m.insert("SpawnX".to_owned(), Tag::from(self.spawn.x));
+        m.insert("SpawnY".to_owned(), Tag::from(self.spawn.y));
+        m.insert("SpawnZ".to_owned(), Tag::from(self.spawn.z));
+
+        if let Some(angle) = self.spawn.angle {
+            m.insert("SpawnAngle".to_owned(), Tag::from(angle));
+        }
+
+        m.insert("raining".to_owned(), Tag::from(self.weather.raining));
+        m.insert("rainTime".to_owned(), Tag::from(self.weather.rain_time));
+        m.insert("thundering".to_owned(), Tag::from(self.weather.thundering));
+        m.insert("thunderTime".to_owned(), Tag::from(self.weather.thunder_time));
+        m.insert("clearWeatherTime".to_owned(), Tag::from(self.weather.clear_weather_time));
+
+        let rules = self.game_rules.iter()
+            .map(|(name, value)| (name.to_owned(), Tag::from(value)))
+            .collect();
+
+        m.insert("GameRules".to_owned(), Tag::Compound(rules));
+
+        if self.has_world_gen_settings() {
+            let mut settings = Map::new();
+
+            settings.insert("seed".to_owned(), Tag::from(self.world_gen.seed));
+            settings.insert("generate_features".to_owned(), Tag::from(self.world_gen.generate_features));
+            settings.insert("bonus_chest".to_owned(), Tag::from(self.world_gen.bonus_chest));
+
+            if let Some(dimensions) = &self.world_gen.dimensions {
+                settings.insert("dimensions".to_owned(), Tag::Compound(dimensions.clone()));
+            }
+
+            for (key, value) in &self.world_gen.extra {
+                settings.insert(key.clone(), value.clone());
+            }
+
+            m.insert("WorldGenSettings".to_owned(), Tag::Compound(settings));
+        } else {
+            m.insert("RandomSeed".to_owned(), Tag::from(self.world_gen.seed));
+            m.insert("MapFeatures".to_owned(), Tag::from(self.world_gen.generate_features));
+        }
+
+        for (key, value) in &self.extra {
+            m.insert(key.clone(), value.clone());
+        }
+
+        Tag::Compound(m)
+    }
+
+    // the file's root is a compound holding only Data
+    pub fn from_nbt(nbt: Nbt) -> Result<Self, NbtError> {
+        let mut m = Map::try_from(nbt.tag)?;
+        let data = take(&mut m, "Data").ok_or(NbtError::MissingField("Data"))?;
+
+        Self::from_tag(data)
+    }
+
+    pub fn to_nbt(&self) -> Nbt {
+        let mut m = Map::new();
+
+        m.insert("Data".to_owned(), self.to_tag());
+
+        Nbt::new(String::new(), Tag::Compound(m))
+    }
+
+    pub fn load<R: Read>(src: &mut R) -> Result<Self, NbtError> {
+        Self::from_nbt(Nbt::decode(src)?)
+    }
+
+    // level.dat is always gzipped
+    pub fn save<W: Write>(&self, dst: &mut W) -> Result<(), NbtError> {
+        self.to_nbt().encode(dst, Compression::Gzip)
+    }
+}
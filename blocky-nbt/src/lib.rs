@@ -3,10 +3,75 @@ extern crate lazy_static;
 
 mod decoder;
 mod encoder;
+mod compression;
+mod dialect;
+mod lz4;
+mod mutf8;
+mod region;
+mod error;
+mod array;
+mod ser;
+mod de;
+mod view;
+mod events;
+mod schema;
+mod block_state;
+mod structure;
+mod level;
+mod fixer;
+mod packed;
+mod chunk;
 #[macro_use]
 mod tag;
 
 pub use tag::*;
+pub use decoder::{Decoder, Limits};
+pub use error::*;
+pub use compression::Compression;
+pub use dialect::Dialect;
+pub use region::Region;
+pub use array::{byte_array, int_array, long_array};
+pub use ser::{to_tag, to_writer, Serializer};
+pub use de::{from_tag, from_reader};
+pub use view::*;
+pub use events::{Event, EventReader, EventWriter};
+pub use schema::{Schema, CompoundSchema};
+pub use block_state::{BlockState, Rotation, Mirror};
+pub use structure::{Structure, StructureBlock, StructureEntity};
+pub use level::*;
+pub use fixer::*;
+pub use packed::*;
+pub use chunk::{Chunk, Section, CHUNK_ROOT_VERSION};
+
+#[cfg(feature = "derive")]
+pub use blocky_nbt_derive::NbtCompound;
+
+// lets the derive's ::blocky_nbt paths resolve in this crate's own tests
+#[cfg(test)]
+extern crate self as blocky_nbt;
+
+// used by code from the NbtCompound derive and the typed models, not public api
+#[doc(hidden)]
+pub mod __private {
+    use crate::{Map, Tag, NbtError};
+    use std::convert::TryFrom;
+
+    // keeps the order of the remaining keys, which flattened fields see
+    #[cfg(feature = "preserve-order")]
+    pub fn take(m: &mut Map<String, Tag>, key: &str) -> Option<Tag> {
+        m.shift_remove(key)
+    }
+
+    #[cfg(not(feature = "preserve-order"))]
+    pub fn take(m: &mut Map<String, Tag>, key: &str) -> Option<Tag> {
+        m.remove(key)
+    }
+
+    // takes a key and converts it, where a missing key is none but a wrong type is an error
+    pub fn get<T: TryFrom<Tag, Error = NbtError>>(m: &mut Map<String, Tag>, key: &str) -> Result<Option<T>, NbtError> {
+        take(m, key).map(T::try_from).transpose()
+    }
+}
 
 use std::str;
 use std::ops;
@@ -33,6 +98,10 @@ impl Nbt {
     pub fn insert<I: Index>(&mut self, index: I, value: Tag) {
         self.tag.insert(index, value);
     }
+
+    pub fn try_insert<I: Index>(&mut self, index: I, value: Tag) -> Result<(), NbtError> {
+        self.tag.try_insert(index, value)
+    }
 }
 
 impl<I: Index> ops::Index<I> for Tag {
@@ -47,8 +116,12 @@ impl<I: Index> ops::Index<I> for Tag {
 }
 
 impl<I: Index> ops::IndexMut<I> for Tag {
+    // panics like indexing a vec when the tag can't be indexed, see Tag::get_mut
     fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        let kind = self.kind();
+
         index.index_or_insert(self)
+            .unwrap_or_else(|| panic!("{}", NbtError::InvalidIndex(kind)))
     }
 }
 
@@ -68,7 +141,9 @@ impl ops::IndexMut<&str> for Nbt {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Kind, Nbt, Tag, tag};
+    use crate::{Kind, Nbt, NbtPath, Tag, Compression, Dialect, NbtError, DecodeError, Limits, SnbtOptions, QuoteStyle, tag};
+    use serde::{Serialize, Deserialize};
+    use std::convert::TryFrom;
     use std::io::Cursor;
 
     fn nbt(data: &[u8]) -> Nbt {
@@ -101,6 +176,207 @@ mod tests {
         nbt(include_bytes!("../examples/compressed.nbt"));
     }
 
+    #[test]
+    fn compression() {
+        let nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
+
+        for &compression in &[Compression::None, Compression::Gzip, Compression::Zlib, Compression::Deflate, Compression::Lz4] {
+            let mut bytes = vec![];
+
+            assert!(nbt.encode(&mut bytes, compression).is_ok(), "failed to encode");
+            assert_eq!(Nbt::decode_with(&mut &bytes[..], compression).unwrap(), nbt);
+
+            if compression != Compression::Deflate {
+                assert_eq!(Compression::detect(&bytes), compression);
+            }
+        }
+
+        // a corrupt gzip stream should fail rather than be read as raw nbt
+        let mut bytes = include_bytes!("../examples/compressed.nbt").to_vec();
+        let len = bytes.len();
+
+        bytes[len / 2..].iter_mut().for_each(|b| *b = !*b);
+
+        assert!(Nbt::decode(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn region() {
+        let dir = std::env::temp_dir().join(format!("blocky-nbt-region-{}", std::process::id()));
+        let path = dir.join(crate::Region::file_name(0, 0));
+
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let chunk = nbt(include_bytes!("../examples/uncompressed.nbt"));
+        let mut big = Nbt::new(String::new(), tag!("{}"));
+
+        // pseudo random longs so the chunk stays larger than 255 sectors after compression
+        big.insert("data", Tag::LongArray((0..200_000i64).map(|i| i.wrapping_mul(0x5deece66d).rotate_left(17)).collect()));
+
+        {
+            let mut region = crate::Region::open(&path).unwrap();
+
+            assert!(region.read_chunk(3, 4).unwrap().is_none());
+
+            region.write_chunk(3, 4, &chunk).unwrap();
+            region.write_chunk(35, 36, &chunk).unwrap();
+            region.write_chunk(0, 0, &big).unwrap();
+        }
+
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        {
+            let mut region = crate::Region::open(&path).unwrap();
+
+            assert!(dir.join("c.0.0.mcc").exists(), "oversized chunk should be external");
+            assert_eq!(region.read_chunk(0, 0).unwrap(), Some(big));
+            assert_eq!(region.read_chunk(3, 4).unwrap(), Some(chunk.clone()));
+
+            // rewriting a chunk reuses its sectors rather than growing the file
+            region.write_chunk(3, 4, &chunk).unwrap();
+            region.remove_chunk(0, 0).unwrap();
+
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+            assert!(!region.has_chunk(0, 0));
+            assert!(!dir.join("c.0.0.mcc").exists());
+
+            let mut region = region.with_compression(Compression::Lz4).unwrap();
+
+            region.write_chunk(5, 5, &chunk).unwrap();
+        }
+
+        let mut region = crate::Region::open(&path).unwrap();
+
+        assert_eq!(region.read_chunk(3, 4).unwrap(), Some(chunk.clone()));
+        assert_eq!(region.read_chunk(5, 5).unwrap(), Some(chunk));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn network() {
+        let nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
+        let mut bytes = vec![];
+
+        assert!(nbt.encode_network(&mut bytes).is_ok(), "failed to encode");
+
+        // the root name is dropped, but nothing after the nbt should be consumed
+        bytes.push(42);
+
+        let mut src = &bytes[..];
+        let decoded = Nbt::decode_network(&mut src).unwrap();
+
+        assert_eq!(decoded.name, "");
+        assert_eq!(decoded.tag, nbt.tag);
+        assert_eq!(src, &[42]);
+    }
+
+    #[test]
+    fn dialects() {
+        let nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
+
+        for &dialect in &[Dialect::Java, Dialect::Bedrock, Dialect::BedrockNetwork] {
+            let mut bytes = vec![];
+
+            assert!(nbt.encode_dialect(&mut bytes, Compression::None, dialect).is_ok(), "failed to encode");
+            assert_eq!(Nbt::decode_dialect(&mut &bytes[..], Compression::None, dialect).unwrap(), nbt);
+        }
+
+        // compound "" { int "a": -2 } with a zigzag varint value
+        let mut bytes = vec![];
+        let mut nbt = Nbt::new(String::new(), tag!("{}"));
+
+        nbt.insert("a", Tag::Int(-2));
+        nbt.encode_dialect(&mut bytes, Compression::None, Dialect::BedrockNetwork).unwrap();
+
+        assert_eq!(bytes, [10, 0, 3, 1, b'a', 3, 0]);
+    }
+
+    #[test]
+    fn modified_utf8() {
+        let mut nbt = Nbt::new(String::new(), tag!("{}"));
+        let mut bytes = vec![];
+
+        nbt.insert("text", Tag::String(String::from("a\0\u{1f600}")));
+        nbt.encode(&mut bytes, Compression::None).unwrap();
+
+        // nul is two bytes and the emoji is a surrogate pair of three byte sequences
+        assert!(bytes.windows(2).any(|w| w == [0xc0, 0x80]));
+        assert!(bytes.windows(6).any(|w| w == [0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]));
+        assert_eq!(Nbt::decode(&mut &bytes[..]).unwrap(), nbt);
+
+        nbt.insert("text", Tag::String("a".repeat(70000)));
+
+        assert!(matches!(nbt.encode(&mut vec![], Compression::None), Err(NbtError::StringTooLong(70000))));
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = include_bytes!("../examples/uncompressed.nbt");
+        let err = Nbt::decode(&mut &bytes[..bytes.len() / 2]).unwrap_err();
+
+        match err {
+            NbtError::Decode { kind, offset, .. } => {
+                assert_eq!(kind, DecodeError::UnexpectedEof);
+                assert!(offset <= bytes.len() as u64 / 2);
+            },
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn hostile_lengths() {
+        // compound "" { list "items" of ints with length -1 }
+        let negative = [10, 0, 0, 9, 0, 5, b'i', b't', b'e', b'm', b's', 3, 255, 255, 255, 255];
+        let err = Nbt::decode(&mut &negative[..]).unwrap_err();
+
+        match err {
+            NbtError::Decode { kind, path, .. } => {
+                assert_eq!(kind, DecodeError::NegativeLength(-1));
+                assert_eq!(path, "items");
+            },
+            _ => panic!("unexpected error: {}", err),
+        }
+
+        // int array claiming far more data than the size limit allows
+        let huge = [11, 0, 0, 0x7f, 0xff, 0xff, 0xff];
+        let err = crate::Decoder::new(&huge[..], Compression::Auto)
+            .unwrap()
+            .with_limits(Limits::network())
+            .decode()
+            .unwrap_err();
+
+        assert!(matches!(err, NbtError::Decode { kind: DecodeError::SizeLimit(_), .. }));
+
+        let limits = Limits { max_depth: 2, ..Limits::default() };
+        let err = crate::Decoder::new(&include_bytes!("../examples/uncompressed.nbt")[..], Compression::Auto)
+            .unwrap()
+            .with_limits(limits)
+            .decode()
+            .unwrap_err();
+
+        assert!(matches!(err, NbtError::Decode { kind: DecodeError::DepthLimit(2), .. }));
+    }
+
+    #[test]
+    fn streaming_progress() {
+        use std::rc::Rc;
+        use std::cell::Cell;
+
+        let bytes = include_bytes!("../examples/compressed.nbt");
+        let progress = Rc::new(Cell::new(0));
+        let mut decoder = crate::Decoder::new(&bytes[..], Compression::Auto)
+            .unwrap()
+            .with_progress({
+                let progress = progress.clone();
+                move |n| progress.set(n)
+            });
+
+        assert!(decoder.decode().is_ok(), "failed to decode nbt");
+        assert_eq!(decoder.bytes_read(), bytes.len() as u64);
+        assert_eq!(progress.get(), bytes.len() as u64);
+    }
+
     #[cfg(feature = "preserve-order")]
     #[test]
     fn insertion_order() {
@@ -108,7 +384,7 @@ mod tests {
         let nbt = nbt(bytes);
         let mut new_bytes = vec![];
 
-        assert!(nbt.encode(&mut new_bytes, false).is_ok(), "failed to encode");
+        assert!(nbt.encode(&mut new_bytes, Compression::None).is_ok(), "failed to encode");
         assert!(bytes.len() == new_bytes.len(), "size doesn't match");
         assert!({
             let len = bytes.len();
@@ -150,13 +426,852 @@ mod tests {
         assert_eq!(compound, "{name:\"Jaden\"}");
     }
 
+    #[test]
+    fn path() {
+        let mut tag = tag!("{Inventory:[{Slot:0b,id:'stone'},{Slot:3b,id:'dirt',tag:{display:{Name:'x'}}}],Pos:[1.0d,2.0d,3.0d]}");
+
+        let path = NbtPath::parse("Inventory[{Slot:3b}].tag.display.Name").unwrap();
+
+        assert_eq!(path.get_all(&tag), [&Tag::String(String::from("x"))]);
+        assert_eq!(NbtPath::parse("Inventory[].id").unwrap().get_all(&tag).len(), 2);
+        assert_eq!(NbtPath::parse("Pos[-1]").unwrap().get_all(&tag), [&Tag::Double(3.0)]);
+        assert_eq!(NbtPath::parse("{Pos:[2.0d]}.Inventory[0].Slot").unwrap().get_all(&tag), [&Tag::Byte(0)]);
+        assert!(NbtPath::parse("{Pos:[4.0d]}.Inventory").unwrap().get_all(&tag).is_empty());
+        assert!(NbtPath::parse("Inventory[5]").unwrap().get_all(&tag).is_empty());
+
+        assert_eq!(path.set(&mut tag, &Tag::String(String::from("y"))), 1);
+        assert_eq!(path.set(&mut tag, &Tag::String(String::from("y"))), 0);
+        assert_eq!(tag["Inventory"][1usize]["tag"]["display"]["Name"], Tag::String(String::from("y")));
+
+        // missing parents are created on write
+        assert_eq!(NbtPath::parse("a.'b c'.d").unwrap().set(&mut tag, &Tag::Int(1)), 1);
+        assert_eq!(tag["a"]["b c"]["d"], Tag::Int(1));
+        assert_eq!(NbtPath::parse("list[].x").unwrap().set(&mut tag, &Tag::Int(2)), 1);
+        assert_eq!(tag["list"], tag!("[{x:2}]"));
+
+        assert_eq!(NbtPath::parse("Pos").unwrap().insert(&mut tag, -1, &Tag::Double(4.0)), 1);
+        assert_eq!(NbtPath::parse("Pos").unwrap().insert(&mut tag, 0, &Tag::Int(0)), 0);
+        assert_eq!(tag["Pos"], tag!("[1.0d,2.0d,3.0d,4.0d]"));
+
+        let merge = match tag!("{display:{Lore:['l']},Damage:1}") {
+            Tag::Compound(m) => m,
+            _ => unreachable!(),
+        };
+
+        assert_eq!(NbtPath::parse("Inventory[{id:'dirt'}].tag").unwrap().merge(&mut tag, &merge), 1);
+        assert_eq!(tag["Inventory"][1usize]["tag"], tag!("{display:{Name:'y',Lore:['l']},Damage:1}"));
+
+        assert_eq!(NbtPath::parse("Inventory[{Slot:0b}]").unwrap().remove(&mut tag), 1);
+        assert_eq!(NbtPath::parse("Inventory[].tag.display").unwrap().remove(&mut tag), 1);
+        assert_eq!(tag["Inventory"], tag!("[{Slot:3b,id:'dirt',tag:{Damage:1}}]"));
+
+        assert!(NbtPath::parse("").is_err());
+        assert!(NbtPath::parse("a..b").is_err());
+        assert!(NbtPath::parse("a[x]").is_err());
+        assert!(NbtPath::parse("a[0]{b:1}").is_err());
+    }
+
+    #[test]
+    fn accessors() {
+        let tag = tag!("{b:1b,s:-2s,l:4294967297L,f:-1.5f,d:2.7d,str:'x',list:[1],flag:2b}");
+
+        assert_eq!(tag["b"].as_i32(), Some(1));
+        assert_eq!(tag["s"].as_i64(), Some(-2));
+        assert_eq!(tag["l"].as_i32(), Some(1));
+        assert_eq!(tag["f"].as_i32(), Some(-2));
+        assert_eq!(tag["f"].as_i64(), Some(-1));
+        assert_eq!(tag["d"].as_i8(), Some(2));
+        assert_eq!(tag["b"].as_f64(), Some(1.0));
+        // rounds once, straight to a float, rather than through a double
+        assert_eq!(Tag::Long(18014399583223809).as_f32(), Some(18014399583223809_i64 as f32));
+        assert_ne!(Tag::Long(18014399583223809).as_f32(), Some(18014399583223809_i64 as f64 as f32));
+        assert_eq!(tag["str"].as_i32(), None);
+        assert_eq!(tag["str"].as_str(), Some("x"));
+        assert_eq!(tag["list"].as_list().map(|v| v.len()), Some(1));
+        assert!(tag.as_compound().is_some());
+        assert!(tag["missing"].as_compound().is_none());
+
+        assert_eq!(tag["b"].as_bool(), Some(true));
+        assert_eq!(tag["flag"].as_bool(), Some(true));
+        assert!(bool::try_from(&tag["b"]).unwrap());
+        assert!(bool::try_from(&tag["flag"]).is_err());
+
+        assert_eq!(i64::try_from(&tag["s"]).unwrap(), -2);
+        assert_eq!(f64::try_from(&tag["f"]).unwrap(), -1.5);
+        assert!(i32::try_from(&tag["l"]).is_err());
+        assert!(matches!(
+            i8::try_from(&tag["str"]),
+            Err(NbtError::WrongType { expected: "byte", found: Kind::String }),
+        ));
+        assert_eq!(<&str>::try_from(&tag["str"]).unwrap(), "x");
+        assert_eq!(Tag::from(true), Tag::Byte(1));
+
+        let mut tag = tag;
+
+        assert!(tag.try_insert("new", Tag::Int(1)).is_ok());
+        assert!(tag["list"].try_insert(0usize, Tag::Int(2)).is_ok());
+        assert!(matches!(tag["list"].try_insert(1usize, Tag::Int(2)), Err(NbtError::InvalidIndex(_))));
+        assert!(tag["b"].try_insert("x", Tag::Int(2)).is_err());
+        assert_eq!(tag["list"], tag!("[2]"));
+    }
+
+    #[test]
+    fn derive() {
+        use blocky_nbt_derive::NbtCompound;
+
+        fn default_count() -> i8 {
+            1
+        }
+
+        #[derive(NbtCompound, Debug, PartialEq)]
+        struct Item {
+            id: String,
+            #[nbt(rename = "Count", default = "default_count")]
+            count: i8,
+        }
+
+        #[derive(NbtCompound, Debug, PartialEq)]
+        struct Position {
+            x: i32,
+            y: i32,
+            // only one field per struct can be flattened, but a flattened struct can flatten another
+            #[nbt(flatten)]
+            rest: crate::Map<String, Tag>,
+        }
+
+        #[derive(NbtCompound, Debug, PartialEq)]
+        struct Player {
+            #[nbt(rename = "Health")]
+            health: f32,
+            #[nbt(rename = "Inventory")]
+            inventory: Vec<Item>,
+            #[nbt(rename = "CustomName")]
+            custom_name: Option<String>,
+            #[nbt(rename = "UUID", int_array)]
+            uuid: Vec<i32>,
+            #[nbt(default)]
+            tags: Vec<String>,
+            #[nbt(flatten)]
+            position: Position,
+        }
+
+        let tag = tag!("{Health: 20.0f, Inventory: [{id: stone, Count: 3b}, {id: dirt}], UUID: [I; 1, 2, 3, 4], x: 5, y: 64, extra: 1b}");
+        let player = Player::try_from(tag).unwrap();
+
+        assert_eq!(player.inventory[1], Item { id: "dirt".into(), count: 1 });
+        assert_eq!(player.custom_name, None);
+        assert!(player.tags.is_empty());
+        assert_eq!((player.position.x, player.position.y), (5, 64));
+        assert_eq!(player.position.rest.get("extra"), Some(&Tag::Byte(1)));
+        assert!(!player.position.rest.contains_key("x"));
+
+        assert_eq!(
+            Tag::from(player),
+            tag!("{Health: 20.0f, Inventory: [{id: stone, Count: 3b}, {id: dirt, Count: 1b}], UUID: [I; 1, 2, 3, 4], tags: [], x: 5, y: 64, extra: 1b}"),
+        );
+
+        assert!(matches!(Item::try_from(tag!("{Count: 1b}")), Err(NbtError::MissingField("id"))));
+        assert!(matches!(Item::try_from(tag!("{id: 1}")), Err(NbtError::WrongType { expected: "string", .. })));
+    }
+
+    #[test]
+    fn view() {
+        use crate::{NbtView, TagRef};
+
+        let tag = tag!("{DataVersion: 3465, Level: {xPos: -2, sections: [{Y: 0b, data: [L; 1L, 2L]}, {Y: 1b, name: 'caf\\u00e9\\u0000'}], heights: [3s, 4s]}}");
+        let nbt = Nbt::new("root".into(), tag.clone());
+        let mut data = vec![];
+
+        nbt.encode(&mut data, Compression::None).unwrap();
+
+        let view = NbtView::new(&data).unwrap();
+
+        assert_eq!(view.name(), "root");
+        assert_eq!(view.index("DataVersion"), TagRef::Int(3465));
+        assert_eq!(view.index("Level").index("xPos").as_i32(), Some(-2));
+        assert_eq!(view.index("Level").index("heights").index(1usize), TagRef::Short(4));
+        assert_eq!(view.index("Level").index("sections".to_string()).index(1usize).index("name").as_str().unwrap(), "caf\u{e9}\0");
+        assert_eq!(view.index("Level").index("sections").index(0usize).index("data"), view.get("Level").unwrap().index("sections").index(0usize).index("data"));
+        assert_eq!(view.index("missing").index(0usize), TagRef::End);
+        assert_eq!(view.to_nbt().unwrap(), nbt);
+
+        match view.index("Level").index("sections").index(0usize).index("data") {
+            TagRef::LongArray(v) => assert_eq!(v.to_vec(), vec![1, 2]),
+            tag => panic!("unexpected {:?}", tag),
+        }
+
+        // only the closing end tag is cut, so every field is still whole in any key order, but
+        // validation reports the missing end
+        let truncated = NbtView::new(&data[..data.len() - 1]).unwrap();
+
+        assert_eq!(truncated.index("DataVersion"), TagRef::Int(3465));
+        assert!(matches!(
+            truncated.validate(),
+            Err(NbtError::Decode { kind: DecodeError::UnexpectedEof, .. }),
+        ));
+    }
+
+    #[test]
+    fn events() {
+        use crate::{Event, EventReader, EventWriter};
+
+        let nbt = Nbt::new("root".into(), tag!("{a: 1b, secret: {key: 'x'}, list: [{b: [I; 1]}, {}], nested: [[1s], []]}"));
+        let mut data = vec![];
+
+        nbt.encode(&mut data, Compression::Gzip).unwrap();
+
+        let events = EventReader::new(&data[..], Compression::Auto).unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        // fields come in the compound's order, which is only stable with preserve-order
+        let contains = |expected: &[Event]| events.windows(expected.len()).any(|w| w == expected);
+
+        assert_eq!(events[0], Event::StartCompound("root".into()));
+        assert!(contains(&[Event::Field("a".into(), Kind::Byte), Event::Primitive(Tag::Byte(1))]));
+        assert!(contains(&[
+            Event::StartCompound("secret".into()),
+            Event::Field("key".into(), Kind::String),
+            Event::Primitive(Tag::String("x".into())),
+            Event::End,
+        ]));
+        assert!(contains(&[Event::Field("list".into(), Kind::List(10)), Event::StartList(Kind::Compound, 2)]));
+        assert_eq!(events.last(), Some(&Event::End));
+
+        // copy everything except the secret compound
+        let mut reader = EventReader::new(&data[..], Compression::Auto).unwrap();
+        let mut writer = EventWriter::new(vec![], Compression::Zlib);
+
+        while let Some(event) = reader.next_event().unwrap() {
+            if event == Event::StartCompound("secret".into()) {
+                reader.skip_subtree().unwrap();
+            } else {
+                writer.write(&event).unwrap();
+            }
+        }
+
+        let data = writer.finish().unwrap();
+        let mut expected = nbt.clone();
+
+        if let Tag::Compound(m) = &mut expected.tag {
+            m.retain(|name, _| name != "secret");
+        }
+
+        assert_eq!(Nbt::decode(&mut &data[..]).unwrap(), expected);
+
+        let mut writer = EventWriter::new(vec![], Compression::None);
+
+        writer.write(&Event::StartCompound(String::new())).unwrap();
+        assert!(matches!(writer.write(&Event::Primitive(Tag::Int(1))), Err(NbtError::UnexpectedEvent(_))));
+        assert!(matches!(writer.finish(), Err(NbtError::UnfinishedEvents)));
+    }
+
+    #[test]
+    fn typed_lists() {
+        use crate::List;
+
+        // {items: [] of compounds}
+        let data = [10, 0, 0, 9, 0, 5, b'i', b't', b'e', b'm', b's', 10, 0, 0, 0, 0, 0];
+        let nbt = Nbt::decode_with(&mut &data[..], Compression::None).unwrap();
+        let mut out = vec![];
+
+        assert_eq!(nbt["items"].kind(), Kind::List(10));
+        assert_eq!(nbt["items"], tag!("[]"));
+        assert!(Tag::diff(&nbt.tag, &tag!("{items: []}")).is_empty());
+        nbt.encode(&mut out, Compression::None).unwrap();
+        assert_eq!(out, data);
+
+        let mut list = List::of(Kind::Int);
+
+        assert!(matches!(list.push(Tag::Byte(1)), Err(NbtError::MixedList)));
+        assert!(list.push(Tag::Int(1)).is_ok());
+        list.clear();
+        assert_eq!(Tag::List(list).kind(), Kind::List(3));
+
+        let mut list = List::new();
+
+        assert!(list.push(Tag::String("a".into())).is_ok());
+        assert!(list.insert(0, Tag::Int(1)).is_err());
+        assert_eq!(list.id(), Kind::String.id());
+
+        // mixed lists built by hand fail to encode instead of writing a corrupt stream
+        let mixed = Nbt::new(String::new(), Tag::from(vec![Tag::Int(1), Tag::Byte(2)]));
+
+        assert!(matches!(mixed.encode(&mut vec![], Compression::None), Err(NbtError::MixedList)));
+        assert!(List::from_tags(3, vec![Tag::Int(1), Tag::Byte(2)]).is_err());
+    }
+
+    #[test]
+    fn schema() {
+        use crate::{Schema, CompoundSchema, SchemaViolation};
+
+        let item = CompoundSchema::new()
+            .required("Slot", Schema::Byte)
+            .required("id", Schema::ResourceLocation)
+            .required("Count", Schema::Byte)
+            .optional("tag", CompoundSchema::new().into());
+
+        let player = Schema::from(CompoundSchema::new()
+            .required("Inventory", Schema::list(item.deny_unknown().into()))
+            .optional("OnGround", Schema::Bool)
+            .optional("UUID", Schema::OneOf(vec![Schema::IntArray, Schema::String])));
+
+        let valid = tag!("{Inventory: [{Slot: 0b, id: 'minecraft:stone', Count: 64b}, {Slot: 1b, id: dirt, Count: 1b, tag: {}}], OnGround: 1b, Other: 5}");
+
+        assert!(player.is_valid(&valid));
+
+        let invalid = tag!("{Inventory: [{Slot: 0s, id: 'Stone', Count: 1b}, {id: 'a:b', Count: 1b, extra: 1}], OnGround: 2b, UUID: 1L}");
+        let errors = player.validate(&invalid);
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+
+        assert_eq!(errors[0].violation, SchemaViolation::WrongType { expected: "byte", found: Kind::Short });
+        assert_eq!(messages[1..], [
+            "Inventory[0].id: invalid resource location \"Stone\": non [a-z0-9/._-] character in path of location",
+            "Inventory[1].Slot: missing required field",
+            "Inventory[1].extra: unknown field",
+            "OnGround: expected 0b or 1b but found 2b",
+            "UUID: matches none of the allowed schemas",
+        ]);
+        assert_eq!(Schema::Int.validate(&Tag::Byte(1))[0].to_string(), "(root): expected int but found Byte");
+
+        // end tags are never written, so they're neither present nor unknown
+        let strict = Schema::from(CompoundSchema::new().required("id", Schema::String).deny_unknown());
+        let mut tag = tag!("{id: stone}");
+
+        tag.insert("gone", Tag::End);
+        assert!(strict.is_valid(&tag));
+    }
+
+    #[test]
+    fn structure() {
+        use crate::{Structure, BlockState, Rotation, Mirror};
+
+        let tag = tag!("{
+            size: [2, 1, 3],
+            palette: [{Name: 'minecraft:stone'}, {Name: 'minecraft:oak_stairs', Properties: {facing: north, half: bottom}}],
+            blocks: [{pos: [0, 0, 0], state: 0}, {pos: [1, 0, 2], state: 1, nbt: {id: 'minecraft:sign'}}],
+            entities: [{pos: [0.5d, 0.0d, 0.5d], blockPos: [0, 0, 0], nbt: {id: 'minecraft:pig', Rotation: [10.0f, 0.0f]}}],
+            DataVersion: 3465,
+            author: 'someone'
+        }");
+
+        let mut structure = Structure::from_tag(tag.clone()).unwrap();
+
+        for state in [-1, 2] {
+            let mut bad = tag.clone();
+
+            bad["blocks"][0usize].insert("state", Tag::Int(state));
+
+            assert!(matches!(Structure::from_tag(bad), Err(NbtError::InvalidPalette(i, 2)) if i == state as i64));
+        }
+
+        assert_eq!(structure.to_tag(), tag);
+        assert_eq!(structure.state_at([1, 0, 2]).unwrap().to_string(), "minecraft:oak_stairs[facing=north,half=bottom]");
+
+        structure.rotate(Rotation::Clockwise90);
+
+        assert_eq!(structure.size, [3, 1, 2]);
+        assert_eq!(structure.blocks[0].pos, [2, 0, 0]);
+        assert_eq!(structure.state_at([0, 0, 1]).unwrap().property("facing"), Some("east"));
+        assert_eq!(structure.entities[0].pos, [2.5, 0.0, 0.5]);
+        assert_eq!(structure.entities[0].nbt["Rotation"][0], Tag::Float(100.0));
+
+        structure.mirror(Mirror::FrontBack);
+
+        assert_eq!(structure.blocks[0].pos, [0, 0, 0]);
+        assert_eq!(structure.state_at([2, 0, 1]).unwrap().property("facing"), Some("west"));
+
+        let stone = BlockState::new("stone".parse().unwrap());
+
+        structure.set_block([1, 0, 1], stone.clone(), None);
+        structure.set_block([0, 0, 0], BlockState::new("dirt".parse().unwrap()), None);
+
+        assert_eq!(structure.palettes[0].len(), 3);
+        assert_eq!(structure.state_at([1, 0, 1]), Some(&stone));
+        assert!(structure.remove_block([2, 0, 1]).is_some());
+        assert_eq!(structure.blocks.len(), 2);
+
+        let mut bytes = vec![];
+
+        structure.save(&mut bytes).unwrap();
+
+        assert_eq!(Structure::load(&mut Cursor::new(bytes)).unwrap(), structure);
+
+        // an index is only shared when every variant holds the state there
+        let dirt = BlockState::new("dirt".parse().unwrap());
+        let mut variants = Structure::new([1, 1, 2]);
+
+        variants.palettes = vec![vec![stone.clone()], vec![dirt.clone()]];
+        variants.set_block([0, 0, 0], stone.clone(), None);
+        variants.set_block([0, 0, 1], stone.clone(), None);
+
+        assert_eq!(variants.palettes, [vec![stone.clone(), stone.clone()], vec![dirt, stone.clone()]]);
+        assert_eq!(variants.blocks.iter().map(|block| block.state).collect::<Vec<_>>(), [1, 1]);
+    }
+
+    #[test]
+    fn level_data() {
+        use crate::{LevelData, GameType};
+
+        let nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
+        let mut level = LevelData::from_nbt(nbt.clone()).unwrap();
+
+        assert_eq!(level.to_nbt(), nbt);
+        assert_eq!(level.world_gen.seed, 4443890602994873962);
+        assert_eq!(level.version.as_ref().unwrap().name, "1.14.1 Pre-Release 2");
+        assert_eq!(level.game_type, GameType::Survival);
+        assert_eq!((level.spawn.x, level.spawn.y, level.spawn.z), (192, 64, -30));
+        assert_eq!(level.weather.rain_time, 24192);
+        assert_eq!(level.game_rules.get_bool("keepInventory"), Some(false));
+        assert_eq!(level.game_rules.get_i32("randomTickSpeed"), Some(3));
+
+        level.game_rules.set("keepInventory", true);
+        level.game_type = GameType::Creative;
+        level.data_version = Some(2586);
+
+        let mut bytes = vec![];
+
+        level.save(&mut bytes).unwrap();
+
+        let saved = Nbt::decode(&mut Cursor::new(&bytes)).unwrap();
+
+        assert_eq!(saved["Data"]["WorldGenSettings"]["seed"], Tag::Long(4443890602994873962));
+        assert_eq!(saved["Data"]["GameRules"]["keepInventory"], Tag::String("true".to_owned()));
+        assert_eq!(saved["Data"]["GameType"], Tag::Int(1));
+        assert!(saved["Data"].get("RandomSeed").is_none());
+        assert_eq!(LevelData::from_nbt(saved).unwrap(), level);
+
+        // settings read without a data version calling for them are written back where they were
+        let data = tag!("{WorldGenSettings: {seed: 5L, generate_features: 1b, bonus_chest: 1b, dimensions: {}}}");
+        let level = LevelData::from_tag(data).unwrap();
+
+        assert!(level.has_world_gen_settings());
+        assert_eq!(level.to_tag()["WorldGenSettings"], tag!("{seed: 5L, generate_features: 1b, bonus_chest: 1b, dimensions: {}}"));
+        assert!(level.to_tag().get("RandomSeed").is_none());
+    }
+
+    #[test]
+    fn data_fixer() {
+        use crate::{DataFixer, LevelData, rename_key};
+
+        let mut structure = Nbt::new(String::new(), tag!("{
+            DataVersion: 1343,
+            palette: [{Name: 'minecraft:stone', Properties: {variant: smooth_granite}}, {Name: 'minecraft:grass'}],
+            entities: [{nbt: {id: 'minecraft:pig', UUIDMost: 1L, UUIDLeast: -2L}}]
+        }"));
+
+        assert_eq!(DataFixer::structure().fixes(1343, 2000).count(), 2);
+        assert_eq!(DataFixer::structure().upgrade(&mut structure, 3700).unwrap(), 1343);
+        assert_eq!(structure.tag, tag!("{
+            DataVersion: 3700,
+            palette: [{Name: 'minecraft:polished_granite'}, {Name: 'minecraft:short_grass'}],
+            entities: [{nbt: {id: 'minecraft:pig', UUID: [I; 0, 1, -1, -2]}}]
+        }"));
+
+        // already at the target, so nothing runs
+        let fixer = DataFixer::new().with_fix(3700, "rename", rename_key("DataVersion", "x"));
+
+        assert_eq!(fixer.upgrade(&mut structure, 3700).unwrap(), 3700);
+        assert!(structure.get("DataVersion").is_some());
+
+        // level.dat keeps its version and data in Data
+        let mut level_nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
+
+        assert_eq!(DataFixer::level().upgrade(&mut level_nbt, 2586).unwrap(), 1956);
+        assert_eq!(level_nbt["Data"]["DataVersion"], Tag::Int(2586));
+        assert!(level_nbt.get("DataVersion").is_none());
+
+        let level = LevelData::from_nbt(level_nbt.clone()).unwrap();
+
+        assert!(level.has_world_gen_settings());
+        assert_eq!(level.world_gen.seed, 4443890602994873962);
+        assert_eq!(level.to_nbt(), level_nbt);
+        assert!(matches!(DataFixer::level().upgrade(&mut Nbt::new(String::new(), tag!("{}")), 2586), Err(NbtError::MissingRoot)));
+
+        let fixer = DataFixer::new().with_fix(2, "flatten", crate::flatten_block_states(vec![]));
+        let err = fixer.upgrade_tag(&mut Tag::Int(1), 1, 2).unwrap_err();
+
+        assert_eq!(err.to_string(), "fix \"flatten\" for data version 2 failed: missing field: Name");
+    }
+
+    #[test]
+    fn packed_arrays() {
+        use crate::{PackedArray, PalettedContainer, Packing, BlockState};
+
+        let mut spanning = PackedArray::new(5, 4096, Packing::Spanning);
+        let mut padded = PackedArray::new(5, 4096, Packing::Padded);
+
+        assert_eq!((spanning.as_longs().len(), padded.as_longs().len()), (320, 342));
+
+        for i in 0..4096 {
+            spanning.set(i, (i % 31) as u64);
+            padded.set(i, (i % 31) as u64);
+        }
+
+        // the thirteenth entry crosses into the second long unless padded
+        assert_eq!(spanning.get(12), 12);
+        assert_eq!(spanning.as_longs()[0] as u64 >> 60, 0b1100);
+        assert_eq!(padded.as_longs()[1] & 0x1f, 12);
+        assert_eq!(spanning.repack(5, Packing::Padded), padded);
+
+        let tag = padded.to_tag();
+
+        assert_eq!(PackedArray::from_tag(&tag, 5, 4096, Packing::Padded).unwrap(), padded);
+        assert!(PackedArray::from_tag(&tag, 5, 4096, Packing::Spanning).is_err());
+        assert_eq!(PackedArray::heightmap(&Tag::LongArray(vec![0; 37]), 384, Packing::Padded).unwrap().bits(), 9);
+
+        let air = BlockState::new("air".parse().unwrap());
+        let mut blocks = PalettedContainer::new(4096, air.clone(), 4, Packing::Padded).with_single_value(true);
+
+        assert_eq!(blocks.bits(), 0);
+        assert_eq!(blocks.to_tag(), tag!("{palette: [{Name: 'minecraft:air'}]}"));
+
+        for i in 0..17 {
+            let state = BlockState::new("stone".parse().unwrap()).with_property("n", i.to_string());
+
+            assert_eq!(blocks.set(i * 100, state), air);
+        }
+
+        assert_eq!(blocks.bits(), 5);
+        assert_eq!(blocks.get(300).property("n"), Some("3"));
+        assert_eq!(PalettedContainer::from_tag(&blocks.to_tag(), 4096, 4).unwrap(), blocks);
+
+        for i in 1..17 {
+            blocks.set(i * 100, air.clone());
+        }
+
+        blocks.compact();
+
+        assert_eq!((blocks.palette().len(), blocks.bits()), (2, 4));
+        assert_eq!(blocks.iter().filter(|state| **state != air).count(), 1);
+        assert!(PalettedContainer::<BlockState>::from_parts(vec![air.clone()], vec![0x20; 256], 4096, 4, Packing::Padded).is_err());
+
+        let mut empty = PalettedContainer::new(0, air.clone(), 4, Packing::Padded);
+
+        empty.compact();
+
+        assert_eq!(empty.palette(), [air]);
+    }
+
+    #[test]
+    fn chunk() {
+        use crate::{Chunk, BlockState};
+
+        let tag = tag!("{
+            DataVersion: 3465, xPos: 2, zPos: -1, yPos: -4, Status: 'minecraft:full',
+            sections: [{Y: -4b, block_states: {palette: [{Name: 'minecraft:bedrock'}]}, biomes: {palette: ['minecraft:plains']}, SkyLight: [B; 0b]}],
+            block_entities: [{id: 'minecraft:chest', x: 33, y: 70, z: -14, Items: []}],
+            Heightmaps: {WORLD_SURFACE: [L; 0L]},
+            InhabitedTime: 0L, isLightOn: 1b
+        }");
+
+        let mut chunk = Chunk::from_tag(tag.clone()).unwrap();
+
+        assert_eq!(chunk.to_tag(), tag);
+        assert_eq!(chunk.block(0, -64, 0).unwrap().name.to_string(), "minecraft:bedrock");
+        assert!(chunk.block(0, 70, 0).is_none());
+        assert!(chunk.block(0, 5000, 0).is_none());
+        assert!(matches!(chunk.set_block(0, -5000, 0, BlockState::new("stone".parse().unwrap())), Err(NbtError::HeightOutOfRange(-5000))));
+
+        let stairs = BlockState::new("oak_stairs".parse().unwrap()).with_property("facing", "east");
+
+        // world coordinates of the chest
+        assert_eq!(chunk.set_block(33, 70, -14, stairs.clone()).unwrap(), None);
+        assert_eq!(chunk.block(1, 70, 2), Some(&stairs));
+        assert_eq!(chunk.block(1, 71, 2).unwrap().name.to_string(), "minecraft:air");
+        assert!(chunk.block_entities.is_empty());
+        assert_eq!(chunk.sections.iter().map(|section| section.y).collect::<Vec<_>>(), [-4, 4]);
+        assert_eq!(chunk.sections[0].block_states.as_ref().unwrap().bits(), 0);
+        assert!(chunk.sections[0].sky_light.is_some());
+        assert!(!chunk.extra.contains_key("isLightOn"));
+
+        chunk.set_block(0, -64, 0, stairs.clone()).unwrap();
+
+        assert!(chunk.sections[0].sky_light.is_none());
+
+        let chunk = Chunk::from_tag(chunk.to_tag()).unwrap();
+
+        assert_eq!(chunk.block(1, 70, 2), Some(&stairs));
+
+        let mut legacy = tag!("{DataVersion: 1976, Level: {xPos: 0, zPos: 0, Sections: [{Y: 0b, Palette: [{Name: 'minecraft:air'}]}], TileEntities: [], Heightmaps: {}}}");
+
+        legacy["Level"]["Sections"][0usize].insert("BlockStates", Tag::LongArray(vec![0; 256]));
+
+        let mut chunk = Chunk::from_tag(legacy.clone()).unwrap();
+
+        assert_eq!(chunk.to_tag(), legacy);
+
+        chunk.set_block(0, 0, 0, stairs.clone()).unwrap();
+
+        assert_eq!(chunk.to_tag()["Level"]["Sections"][0usize]["BlockStates"].as_long_array().map(|v| v[0]), Some(1));
+    }
+
+    #[test]
+    fn merge_diff_patch() {
+        let mut tag = tag!("{a:{b:1,c:[1,2]},d:'x'}");
+
+        tag.merge(&tag!("{a:{c:[3],e:2b},d:{}}"));
+
+        assert_eq!(tag, tag!("{a:{b:1,c:[3],e:2b},d:{}}"));
+
+        let before = tag!("{Health:20.0f,Pos:[0.0d,64.0d,0.0d],Inventory:[{id:'stone'}],'odd key':1,gone:1b}");
+        let after = tag!("{Health:18.5f,Pos:[0.0d,70.0d,0.0d],Inventory:[{id:'stone'},{id:'dirt'}],'odd key':1,new:{x:'a/b'}}");
+        let patch = Tag::diff(&before, &after);
+
+        assert_eq!(patch.changes.len(), 5);
+        assert_eq!(patch.to_string(), concat!(
+            "~ Health: 20.0f -> 18.5f\n",
+            "~ Inventory: [{id:\"stone\"}] -> [{id:\"stone\"},{id:\"dirt\"}]\n",
+            "~ Pos[1]: 64.0d -> 70.0d\n",
+            "- gone: 1b\n",
+            "+ new: {x:\"a/b\"}\n",
+        ));
+        assert_eq!(patch.to_json(), concat!(
+            r#"[{"op":"replace","path":"/Health","value":18.5},"#,
+            r#"{"op":"replace","path":"/Inventory","value":[{"id":"stone"},{"id":"dirt"}]},"#,
+            r#"{"op":"replace","path":"/Pos/1","value":70},"#,
+            r#"{"op":"remove","path":"/gone"},"#,
+            r#"{"op":"add","path":"/new","value":{"x":"a/b"}}]"#,
+        ));
+
+        let mut patched = before.clone();
+
+        patch.apply(&mut patched).unwrap();
+
+        assert_eq!(patched, after);
+        assert!(Tag::diff(&after, &patched).is_empty());
+
+        // removing something already gone is a mismatch
+        assert!(matches!(patch.apply(&mut patched), Err(NbtError::PatchMismatch(_))));
+    }
+
+    #[test]
+    fn snbt_grammar() {
+        assert_eq!(Nbt::parse("255ub").unwrap(), Tag::Byte(-1));
+        assert_eq!(Nbt::parse("-5sb").unwrap(), Tag::Byte(-5));
+        assert_eq!(Nbt::parse("65535us").unwrap(), Tag::Short(-1));
+        assert_eq!(Nbt::parse("0xFFFFFFFF").unwrap(), Tag::Int(-1));
+        assert_eq!(Nbt::parse("0xFFb").unwrap(), Tag::Int(0xffb));
+        assert_eq!(Nbt::parse("0x7fub").unwrap(), Tag::Byte(127));
+        assert_eq!(Nbt::parse("0b1010s").unwrap(), Tag::Short(10));
+        assert_eq!(Nbt::parse("-0x10L").unwrap(), Tag::Long(-16));
+        assert_eq!(Nbt::parse("1_000_000").unwrap(), Tag::Int(1_000_000));
+        assert_eq!(Nbt::parse("1_000.5_5e1_0").unwrap(), Tag::Double(1000.55e10));
+        assert_eq!(Nbt::parse("5i").unwrap(), Tag::Int(5));
+        assert_eq!(Nbt::parse("1e3").unwrap(), Tag::Double(1000.0));
+        assert_eq!(Nbt::parse("_1").unwrap(), Tag::String(String::from("_1")));
+
+        assert!(Nbt::parse("256ub").is_err());
+        assert!(Nbt::parse("-1ub").is_err());
+        assert!(Nbt::parse("2147483648").is_err());
+
+        assert_eq!(Nbt::parse(r#""a\nb\tcé\x41\s\U0001F600""#).unwrap(), Tag::String(String::from("a\nb\tcéA \u{1f600}")));
+        assert!(Nbt::parse(r#""\q""#).is_err());
+        assert_eq!(Nbt::parse(r#""it\'s \"x\"""#).unwrap(), Tag::String(String::from("it's \"x\"")));
+        assert_eq!(Nbt::parse(r#"'it\'s \"x\"'"#).unwrap(), Tag::String(String::from("it's \"x\"")));
+        assert!(Nbt::parse(r#""\N{SNOWMAN}""#).unwrap_err().to_string().contains("named escapes"));
+        assert!(Nbt::parse(r#""\uD800""#).is_err());
+
+        let text = Tag::String(String::from("line\nbreak\u{1}\t'\""));
+
+        assert_eq!(Nbt::parse(text.to_string()).unwrap(), text);
+
+        assert_eq!(Nbt::parse("bool(true)").unwrap(), Tag::Byte(1));
+        assert_eq!(Nbt::parse("bool(0.0)").unwrap(), Tag::Byte(0));
+        assert_eq!(Nbt::parse("uuid(f81d4fae-7dec-11d0-a765-00a0c91e6bf6)").unwrap(), Tag::IntArray(vec![
+            0xf81d4faeu32 as i32, 0x7dec11d0, 0xa76500a0u32 as i32, 0xc91e6bf6u32 as i32,
+        ]));
+        assert!(Nbt::parse("uuid(nope)").is_err());
+        assert!(Nbt::parse("frobnicate(1)").is_err());
+
+        // mixed lists wrap their elements in compounds with an empty key
+        assert_eq!(Nbt::parse("[1, 'a', {b: 2}]").unwrap(), tag!("[{'': 1}, {'': 'a'}, {b: 2}]"));
+        assert_eq!(Nbt::parse("[[1], ['a']]").unwrap(), Tag::from(vec![
+            Tag::from(vec![Tag::Int(1)]),
+            Tag::from(vec![Tag::String(String::from("a"))]),
+        ]));
+
+        assert_eq!(Nbt::parse("{a: 1} trailing").unwrap(), tag!("{a: 1}"));
+        assert_eq!(Nbt::parse_strict(" {a: 1} ").unwrap(), tag!("{a: 1}"));
+        assert!(Nbt::parse_strict("{a: 1} trailing").is_err());
+    }
+
+    #[test]
+    fn parse_error() {
+        let error = match Nbt::parse("{\n  name: 'é',\n  list: [I; 1, 2b]\n}") {
+            Err(NbtError::Syntax(error)) => error,
+            other => panic!("expected syntax error, got {:?}", other),
+        };
+
+        assert_eq!((error.offset, error.line, error.column), (30, 3, 16));
+        assert_eq!(error.snippet, "  list: [I; 1, 2b]\n               ^");
+
+        let error = match Nbt::parse("{a:1 b:2}") {
+            Err(NbtError::Syntax(error)) => error,
+            other => panic!("expected syntax error, got {:?}", other),
+        };
+
+        assert_eq!(error.expected, [",", "}"]);
+        assert_eq!(error.to_string(), "unexpected b at line 1, column 6 (expected one of `,`, `}`)\n{a:1 b:2}\n     ^");
+
+        // multibyte chars are counted once, so reading past them doesn't go out of bounds
+        assert_eq!(Nbt::parse("'ééé'").unwrap(), Tag::String(String::from("ééé")));
+        assert!(Nbt::parse("[ééé").is_err());
+    }
+
+    #[test]
+    fn float_snbt() {
+        assert_eq!(Tag::Float(1.0).to_string(), "1.0f");
+        assert_eq!(Tag::Double(-0.5).to_string(), "-0.5d");
+        assert_eq!(Tag::Double(1e300).to_string(), "1.0E300d");
+        assert_eq!(Tag::Float(1.5e-7).to_string(), "1.5E-7f");
+        assert_eq!(Tag::Double(f64::NAN).to_string(), "NaNd");
+        assert_eq!(Tag::Float(f32::NEG_INFINITY).to_string(), "-Infinityf");
+
+        let floats = [0.0, -0.0, 0.1, 1.0 / 3.0, 1e7, 9999999.0, f32::MAX, f32::MIN_POSITIVE, 1e-45, f32::INFINITY];
+        let doubles = [0.0, -0.0, 0.1, 1.0 / 3.0, 1e7, 123456789.125, f64::MAX, f64::MIN_POSITIVE, 5e-324, f64::NEG_INFINITY];
+
+        for value in floats.iter() {
+            let tag = Nbt::parse(Tag::Float(*value).to_string()).unwrap();
+
+            assert!(matches!(tag, Tag::Float(v) if v.to_bits() == value.to_bits()), "{} did not round trip", value);
+        }
+
+        for value in doubles.iter() {
+            let tag = Nbt::parse(Tag::Double(*value).to_string()).unwrap();
+
+            assert!(matches!(tag, Tag::Double(v) if v.to_bits() == value.to_bits()), "{} did not round trip", value);
+        }
+
+        assert!(matches!(Nbt::parse("NaNf").unwrap(), Tag::Float(v) if v.is_nan()));
+        assert_eq!(Nbt::parse("1.5E3").unwrap(), Tag::Double(1500.0));
+    }
+
+    #[test]
+    fn pretty_snbt() {
+        // keys are sorted so the output doesn't depend on preserve-order
+        let tag = tag!("{b:1b,a:{name:'x',list:[1,2,3]},empty:{}}");
+        let sorted = SnbtOptions {
+            sort_keys: true,
+            ..SnbtOptions::default()
+        };
+
+        assert_eq!(tag.to_snbt_with(&SnbtOptions { sort_keys: true, ..SnbtOptions::pretty(2) }).unwrap(), concat!(
+            "{\n",
+            "  a: {\n",
+            "    list: [1, 2, 3],\n",
+            "    name: \"x\"\n",
+            "  },\n",
+            "  b: 1b,\n",
+            "  empty: {}\n",
+            "}",
+        ));
+
+        let options = SnbtOptions {
+            quote: QuoteStyle::Single,
+            bool_bytes: true,
+            ..sorted.clone()
+        };
+
+        assert_eq!(tag.to_snbt_with(&options).unwrap(), "{a:{list:[1,2,3],name:'x'},b:true,empty:{}}");
+
+        let options = SnbtOptions {
+            max_inline_width: 8,
+            ..SnbtOptions::pretty(4)
+        };
+
+        assert_eq!(tag!("[L;1L,2L,3L]").to_snbt_with(&options).unwrap(), "[L;\n    1L,\n    2L,\n    3L\n]");
+        assert_eq!(tag!("[L;1L]").to_snbt_with(&options).unwrap(), "[L;1L]");
+        assert_eq!(tag!("{only:{one:1b}}").to_snbt_pretty(2).unwrap(), "{\n  only: {\n    one: 1b\n  }\n}");
+
+        let mut tag = tag;
+
+        tag.insert("end", Tag::End);
+
+        assert_eq!(tag.to_snbt_with(&sorted).unwrap(), "{a:{list:[1,2,3],name:\"x\"},b:1b,empty:{}}");
+        assert!(matches!(Tag::End.to_snbt(), Err(NbtError::EncodeEnd)));
+        assert_eq!(Tag::End.to_string(), "END");
+        assert_eq!(format!("{}", Tag::from(vec![Tag::End])), "[END]");
+        assert_eq!(Tag::diff(&Tag::from(vec![Tag::Int(1)]), &Tag::from(vec![Tag::End])).to_string(), "~ : [1] -> [END]\n");
+        assert!(Tag::from(vec![Tag::End]).to_snbt().is_err());
+    }
+
     #[test]
     fn verify_value() {
         let nbt = nbt(include_bytes!("../examples/uncompressed.nbt"));
-        let seed: i64 = (&nbt["Data"]["RandomSeed"]).into();
-        let version_name: String = (&nbt["Data"]["Version"]["Name"]).into();
+        let seed = i64::try_from(&nbt["Data"]["RandomSeed"]).unwrap();
+        let version_name = String::try_from(&nbt["Data"]["Version"]["Name"]).unwrap();
 
         assert_eq!(seed, 4443890602994873962);
         assert_eq!(version_name, "1.14.1 Pre-Release 2");
     }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Player {
+        name: String,
+        health: f32,
+        on_ground: bool,
+        xp: Option<i32>,
+        inventory: Vec<Item>,
+        #[serde(with = "crate::int_array")]
+        uuid: Vec<i32>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: String,
+        count: u8,
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let player = Player {
+            name: String::from("Jaden"),
+            health: 20.0,
+            on_ground: true,
+            xp: None,
+            inventory: vec![Item { id: String::from("minecraft:stone"), count: 200 }],
+            uuid: vec![1, 2, 3, 4],
+        };
+
+        let tag = crate::to_tag(&player).unwrap();
+
+        assert_eq!(tag["uuid"], Tag::IntArray(vec![1, 2, 3, 4]));
+        assert_eq!(tag["on_ground"], Tag::Byte(1));
+        assert!(tag.get("xp").is_none(), "none should be skipped");
+
+        let mut bytes = vec![];
+
+        assert!(crate::to_writer(&mut bytes, &player, Compression::Gzip).is_ok(), "failed to encode");
+
+        let decoded: Player = crate::from_reader(&mut Cursor::new(bytes)).unwrap();
+
+        assert_eq!(decoded, player);
+
+        #[derive(Serialize)]
+        struct Marker {
+            unit: (),
+        }
+
+        assert!(matches!(crate::to_tag(&Marker { unit: () }), Err(NbtError::UnsupportedType("unit"))));
+    }
+
+    #[test]
+    fn serde_level() {
+        #[derive(Deserialize)]
+        struct Level {
+            #[serde(rename = "Data")]
+            data: Data,
+        }
+
+        #[derive(Deserialize)]
+        struct Data {
+            #[serde(rename = "RandomSeed")]
+            seed: i64,
+        }
+
+        let level: Level = crate::from_reader(&mut Cursor::new(&include_bytes!("../examples/uncompressed.nbt")[..])).unwrap();
+
+        assert_eq!(level.data.seed, 4443890602994873962);
+    }
 }
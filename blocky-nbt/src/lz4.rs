@@ -0,0 +1,193 @@
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use twox_hash::XxHash32;
+
+// minecraft uses the block stream format written by lz4-java's LZ4BlockOutputStream
+
+pub(crate) const MAGIC: &[u8; 8] = b"LZ4Block";
+
+const SEED: u32 = 0x9747_b28c;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+const BLOCK_SIZE: usize = 1 << 16;
+// log2(BLOCK_SIZE) - 10, as stored in the low bits of the token
+const LEVEL: u8 = 6;
+const HEADER_LEN: usize = MAGIC.len() + 13;
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(SEED);
+
+    hasher.write(data);
+    hasher.finish() as u32 & 0x0fff_ffff
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct Lz4Reader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    position: usize,
+    finished: bool,
+}
+
+impl<R> Lz4Reader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![],
+            position: 0,
+            finished: false,
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+}
+
+impl<R: Read> Lz4Reader<R> {
+    fn read_block(&mut self) -> io::Result<()> {
+        let mut header = [0; HEADER_LEN];
+
+        self.inner.read_exact(&mut header)?;
+
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data("invalid lz4 block magic"));
+        }
+
+        let token = header[8];
+        let compressed_len = u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+        let decompressed_len = u32::from_le_bytes([header[13], header[14], header[15], header[16]]) as usize;
+        let expected = u32::from_le_bytes([header[17], header[18], header[19], header[20]]);
+
+        // an empty block marks the end of the stream
+        if decompressed_len == 0 {
+            self.finished = true;
+            self.buf.clear();
+            self.position = 0;
+
+            return Ok(());
+        }
+
+        // the token's level gives the block size the stream was written with
+        let max_len = 1 << (10 + (token & 0x0f) as usize);
+
+        if decompressed_len > max_len || compressed_len > decompressed_len + (decompressed_len >> 8) + 16 {
+            return Err(invalid_data("invalid lz4 block length"));
+        }
+
+        let mut compressed = vec![0; compressed_len];
+
+        self.inner.read_exact(&mut compressed)?;
+
+        self.buf = match token & 0xf0 {
+            METHOD_RAW => compressed,
+            METHOD_LZ4 => lz4_flex::block::decompress(&compressed, decompressed_len)
+                .map_err(|_| invalid_data("corrupt lz4 block"))?,
+            _ => return Err(invalid_data("unknown lz4 compression method")),
+        };
+        self.position = 0;
+
+        if self.buf.len() != decompressed_len || checksum(&self.buf) != expected {
+            return Err(invalid_data("lz4 block checksum mismatch"));
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Lz4Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.buf.len() {
+            if self.finished {
+                return Ok(0);
+            }
+
+            self.read_block()?;
+        }
+
+        let n = buf.len().min(self.buf.len() - self.position);
+
+        buf[..n].copy_from_slice(&self.buf[self.position..self.position + n]);
+        self.position += n;
+
+        Ok(n)
+    }
+}
+
+pub struct Lz4Writer<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> Lz4Writer<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(BLOCK_SIZE),
+        }
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        let compressed = lz4_flex::block::compress(&self.buf);
+
+        // store the block raw when compressing doesn't help
+        let (method, data) = if compressed.len() < self.buf.len() {
+            (METHOD_LZ4, &compressed[..])
+        } else {
+            (METHOD_RAW, &self.buf[..])
+        };
+
+        self.inner.write_all(MAGIC)?;
+        self.inner.write_all(&[method | LEVEL])?;
+        self.inner.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&(self.buf.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&checksum(&self.buf).to_le_bytes())?;
+        self.inner.write_all(data)?;
+
+        self.buf.clear();
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.buf.is_empty() {
+            self.write_block()?;
+        }
+
+        // write the empty end block
+        self.inner.write_all(MAGIC)?;
+        self.inner.write_all(&[METHOD_RAW | LEVEL])?;
+        self.inner.write_all(&[0; 12])?;
+
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for Lz4Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK_SIZE - self.buf.len());
+
+        self.buf.extend_from_slice(&buf[..n]);
+
+        if self.buf.len() == BLOCK_SIZE {
+            self.write_block()?;
+        }
+
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.write_block()?;
+        }
+
+        self.inner.flush()
+    }
+}
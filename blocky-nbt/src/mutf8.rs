@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+// java writes strings with the modified utf-8 of DataOutput.writeUTF, where nul is two bytes
+// and supplementary characters are written as a surrogate pair of three byte sequences
+
+pub fn encode(s: &str) -> Cow<'_, [u8]> {
+    // without nul or supplementary characters both encodings are the same
+    if !s.chars().any(|chr| chr == '\0' || chr as u32 > 0xffff) {
+        return Cow::Borrowed(s.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(s.len() + 8);
+
+    for unit in s.encode_utf16() {
+        match unit {
+            0x0001..=0x007f => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07ff => {
+                bytes.push(0xc0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            },
+            _ => {
+                bytes.push(0xe0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+                bytes.push(0x80 | (unit & 0x3f) as u8);
+            },
+        }
+    }
+
+    Cow::Owned(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    if bytes.iter().all(|b| *b != 0 && *b < 0x80) {
+        return String::from_utf8(bytes.to_vec()).ok();
+    }
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i] as u16;
+
+        if b < 0x80 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xe0 == 0xc0 {
+            let b2 = *bytes.get(i + 1)? as u16;
+
+            if b2 & 0xc0 != 0x80 {
+                return None;
+            }
+
+            units.push((b & 0x1f) << 6 | (b2 & 0x3f));
+            i += 2;
+        } else if b & 0xf0 == 0xe0 {
+            let b2 = *bytes.get(i + 1)? as u16;
+            let b3 = *bytes.get(i + 2)? as u16;
+
+            if b2 & 0xc0 != 0x80 || b3 & 0xc0 != 0x80 {
+                return None;
+            }
+
+            units.push((b & 0x0f) << 12 | (b2 & 0x3f) << 6 | (b3 & 0x3f));
+            i += 3;
+        } else {
+            return None;
+        }
+    }
+
+    // java strings may hold unpaired surrogates, which rust strings can't
+    Some(String::from_utf16_lossy(&units))
+}
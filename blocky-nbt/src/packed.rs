@@ -0,0 +1,424 @@
+use crate::{Tag, List, Map, NbtError, BlockState};
+use blocky_core::ResourceLocation;
+use std::convert::TryFrom;
+
+// the first data version to pad packed arrays instead of letting entries span two longs
+pub const PADDED_PACKING_VERSION: i32 = 2527;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packing {
+    // entries follow each other bit by bit, crossing from one long into the next
+    Spanning,
+    // each long holds as many whole entries as fit, leaving the rest of its bits unused
+    Padded,
+}
+
+impl Packing {
+    pub fn for_version(data_version: i32) -> Self {
+        if data_version >= PADDED_PACKING_VERSION {
+            Self::Padded
+        } else {
+            Self::Spanning
+        }
+    }
+}
+
+// the number of bits needed to store values up to max
+pub fn bits_needed(max: u64) -> u32 {
+    64 - max.leading_zeros()
+}
+
+fn longs_needed(bits: u32, len: usize, packing: Packing) -> usize {
+    let bits = bits as usize;
+
+    match (bits, packing) {
+        (0, _) => 0,
+        (_, Packing::Spanning) => (len * bits).div_ceil(64),
+        (_, Packing::Padded) => len.div_ceil(64 / bits),
+    }
+}
+
+// fixed width unsigned entries packed into longs, as used for block states, biomes and
+// heightmaps. a width of zero stores nothing and reads every entry as zero
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedArray {
+    bits: u32,
+    len: usize,
+    packing: Packing,
+    data: Vec<i64>,
+}
+
+impl PackedArray {
+    // panics if bits is over 32, which no vanilla array needs
+    pub fn new(bits: u32, len: usize, packing: Packing) -> Self {
+        assert!(bits <= 32, "packed entries can't be wider than 32 bits");
+
+        Self {
+            bits,
+            len,
+            packing,
+            data: vec![0; longs_needed(bits, len, packing)],
+        }
+    }
+
+    pub fn from_longs(data: Vec<i64>, bits: u32, len: usize, packing: Packing) -> Result<Self, NbtError> {
+        let mut array = Self::new(bits, len, packing);
+
+        if data.len() != array.data.len() {
+            return Err(NbtError::PackedLength {
+                expected: array.data.len(),
+                found: data.len(),
+            });
+        }
+
+        array.data = data;
+
+        Ok(array)
+    }
+
+    pub fn from_tag(tag: &Tag, bits: u32, len: usize, packing: Packing) -> Result<Self, NbtError> {
+        match tag {
+            Tag::LongArray(data) => Self::from_longs(data.clone(), bits, len, packing),
+            _ => Err(tag.wrong_type("long array")),
+        }
+    }
+
+    // heightmaps store heights from 0 up to the world's height, inclusive
+    pub fn heightmap(tag: &Tag, world_height: u32, packing: Packing) -> Result<Self, NbtError> {
+        Self::from_tag(tag, bits_needed(world_height as u64), 256, packing)
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        Tag::LongArray(self.data.clone())
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn packing(&self) -> Packing {
+        self.packing
+    }
+
+    pub fn as_longs(&self) -> &[i64] {
+        &self.data
+    }
+
+    pub fn into_longs(self) -> Vec<i64> {
+        self.data
+    }
+
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+
+    // the long holding an entry and the offset of its lowest bit
+    fn locate(&self, index: usize) -> (usize, u32) {
+        assert!(index < self.len, "index {} out of range for packed array of length {}", index, self.len);
+
+        let bits = self.bits as usize;
+
+        match self.packing {
+            Packing::Spanning => ((index * bits) / 64, ((index * bits) % 64) as u32),
+            Packing::Padded => {
+                let per_long = 64 / bits;
+
+                (index / per_long, ((index % per_long) * bits) as u32)
+            },
+        }
+    }
+
+    // panics if the index is out of range, like indexing a slice
+    pub fn get(&self, index: usize) -> u64 {
+        if self.bits == 0 {
+            assert!(index < self.len, "index {} out of range for packed array of length {}", index, self.len);
+            return 0;
+        }
+
+        let (i, offset) = self.locate(index);
+        let mut value = self.data[i] as u64 >> offset;
+
+        if offset + self.bits > 64 {
+            value |= (self.data[i + 1] as u64) << (64 - offset);
+        }
+
+        value & self.mask()
+    }
+
+    // panics if the index is out of range or the value doesn't fit in the width
+    pub fn set(&mut self, index: usize, value: u64) {
+        assert!(value <= self.mask(), "value {} doesn't fit in {} bits", value, self.bits);
+
+        if self.bits == 0 {
+            assert!(index < self.len, "index {} out of range for packed array of length {}", index, self.len);
+            return;
+        }
+
+        let (i, offset) = self.locate(index);
+        let mask = self.mask();
+
+        self.data[i] = ((self.data[i] as u64 & !(mask << offset)) | (value << offset)) as i64;
+
+        if offset + self.bits > 64 {
+            let high = 64 - offset;
+
+            self.data[i + 1] = ((self.data[i + 1] as u64 & !(mask >> high)) | (value >> high)) as i64;
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.len).map(move |i| self.get(i))
+    }
+
+    // the same entries at another width or packing, panicking if one doesn't fit
+    pub fn repack(&self, bits: u32, packing: Packing) -> Self {
+        let mut array = Self::new(bits, self.len, packing);
+
+        for (i, value) in self.iter().enumerate() {
+            array.set(i, value);
+        }
+
+        array
+    }
+}
+
+// what a palette can hold, read from and written to the tags in its palette list
+pub trait PaletteEntry: Sized + Clone + PartialEq {
+    fn from_tag(tag: &Tag) -> Result<Self, NbtError>;
+    fn to_tag(&self) -> Tag;
+}
+
+impl PaletteEntry for BlockState {
+    fn from_tag(tag: &Tag) -> Result<Self, NbtError> {
+        BlockState::from_tag(tag)
+    }
+
+    fn to_tag(&self) -> Tag {
+        BlockState::to_tag(self)
+    }
+}
+
+// biomes
+impl PaletteEntry for ResourceLocation {
+    fn from_tag(tag: &Tag) -> Result<Self, NbtError> {
+        let s = <&str>::try_from(tag)?;
+
+        s.parse().map_err(|_| NbtError::InvalidResourceLocation(s.to_owned()))
+    }
+
+    fn to_tag(&self) -> Tag {
+        Tag::String(self.to_string())
+    }
+}
+
+// entries stored as indices into a palette, widening the indices as the palette grows
+#[derive(Debug, Clone, PartialEq)]
+pub struct PalettedContainer<T> {
+    palette: Vec<T>,
+    data: PackedArray,
+    min_bits: u32,
+    single_value: bool,
+}
+
+impl<T: PaletteEntry> PalettedContainer<T> {
+    // every entry starts as value. indices are at least min_bits wide, which is 4 for block
+    // states in every version and 1 for biomes
+    pub fn new(len: usize, value: T, min_bits: u32, packing: Packing) -> Self {
+        Self {
+            palette: vec![value],
+            data: PackedArray::new(min_bits, len, packing),
+            min_bits,
+            single_value: false,
+        }
+    }
+
+    // since 1.18 a palette of a single entry is stored without any data
+    pub fn with_single_value(mut self, single_value: bool) -> Self {
+        self.single_value = single_value;
+        self.data = self.data.repack(self.bits_for(self.palette.len()), self.data.packing);
+        self
+    }
+
+    fn bits_for(&self, palette_len: usize) -> u32 {
+        match bits_needed(palette_len.saturating_sub(1) as u64) {
+            0 if self.single_value => 0,
+            bits => bits.max(self.min_bits),
+        }
+    }
+
+    // data is empty for a single entry palette stored without data
+    pub fn from_parts(palette: Vec<T>, data: Vec<i64>, len: usize, min_bits: u32, packing: Packing) -> Result<Self, NbtError> {
+        let mut container = Self {
+            palette,
+            data: PackedArray::new(0, len, packing),
+            min_bits,
+            single_value: data.is_empty(),
+        };
+
+        if container.palette.is_empty() {
+            return Err(NbtError::InvalidPalette(0, 0));
+        }
+
+        let bits = container.bits_for(container.palette.len());
+
+        container.data = PackedArray::from_longs(data, bits, len, packing)?;
+
+        if let Some(index) = container.data.iter().find(|index| *index as usize >= container.palette.len()) {
+            return Err(NbtError::InvalidPalette(index as i64, container.palette.len()));
+        }
+
+        Ok(container)
+    }
+
+    // the separate palette list and long array of sections from before 1.18
+    pub fn from_lists(palette: &Tag, data: &Tag, len: usize, min_bits: u32, packing: Packing) -> Result<Self, NbtError> {
+        let palette = palette.as_list()
+            .ok_or_else(|| palette.wrong_type("list"))?
+            .iter()
+            .map(T::from_tag)
+            .collect::<Result<_, _>>()?;
+
+        let data = match data {
+            Tag::LongArray(data) => data.clone(),
+            _ => return Err(data.wrong_type("long array")),
+        };
+
+        let mut container = Self::from_parts(palette, data, len, min_bits, packing)?;
+
+        container.single_value = false;
+
+        Ok(container)
+    }
+
+    // the compound with palette and data used since 1.18
+    pub fn from_tag(tag: &Tag, len: usize, min_bits: u32) -> Result<Self, NbtError> {
+        let palette = tag.get("palette").ok_or(NbtError::MissingField("palette"))?;
+        let palette = palette.as_list()
+            .ok_or_else(|| palette.wrong_type("list"))?
+            .iter()
+            .map(T::from_tag)
+            .collect::<Result<_, _>>()?;
+
+        let data = match tag.get("data") {
+            Some(Tag::LongArray(data)) => data.clone(),
+            Some(tag) => return Err(tag.wrong_type("long array")),
+            None => vec![],
+        };
+
+        let mut container = Self::from_parts(palette, data, len, min_bits, Packing::Padded)?;
+
+        container.single_value = true;
+
+        Ok(container)
+    }
+
+    pub fn palette_tag(&self) -> Tag {
+        Tag::List(self.palette.iter().map(T::to_tag).collect::<List>())
+    }
+
+    pub fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("palette".to_owned(), self.palette_tag());
+
+        if self.data.bits > 0 {
+            m.insert("data".to_owned(), self.data.to_tag());
+        }
+
+        Tag::Compound(m)
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.len == 0
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.data.bits
+    }
+
+    pub fn palette(&self) -> &[T] {
+        &self.palette
+    }
+
+    pub fn data(&self) -> &PackedArray {
+        &self.data
+    }
+
+    pub fn get(&self, index: usize) -> &T {
+        &self.palette[self.data.get(index) as usize]
+    }
+
+    // returns the entry that was there, adding value to the palette if it's new
+    pub fn set(&mut self, index: usize, value: T) -> T {
+        let old = self.get(index).clone();
+
+        let i = match self.palette.iter().position(|entry| *entry == value) {
+            Some(i) => i,
+            None => {
+                self.palette.push(value);
+
+                let bits = self.bits_for(self.palette.len());
+
+                if bits > self.data.bits {
+                    self.data = self.data.repack(bits, self.data.packing);
+                }
+
+                self.palette.len() - 1
+            },
+        };
+
+        self.data.set(index, i as u64);
+
+        old
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.data.iter().map(move |i| &self.palette[i as usize])
+    }
+
+    // drops palette entries nothing uses any more and narrows the indices to match
+    pub fn compact(&mut self) {
+        let mut used = vec![false; self.palette.len()];
+
+        for i in self.data.iter() {
+            used[i as usize] = true;
+        }
+
+        // a palette is never empty, so a container without entries keeps its first one
+        if self.data.is_empty() {
+            used[0] = true;
+        }
+
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = vec![];
+
+        for (i, entry) in self.palette.drain(..).enumerate() {
+            if used[i] {
+                remap[i] = palette.len() as u64;
+                palette.push(entry);
+            }
+        }
+
+        self.palette = palette;
+
+        let mut data = PackedArray::new(self.bits_for(self.palette.len()), self.data.len, self.data.packing);
+
+        for (i, index) in self.data.iter().enumerate() {
+            data.set(i, remap[index as usize]);
+        }
+
+        self.data = data;
+    }
+}
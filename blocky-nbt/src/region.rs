@@ -0,0 +1,313 @@
+use crate::{Nbt, Compression, NbtError};
+use crate::decoder::decompress;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECTOR_SIZE: usize = 4096;
+const CHUNKS: usize = 1024;
+const HEADER_SECTORS: usize = 2;
+// chunks needing more sectors than fit in a location entry are stored in a .mcc file
+const MAX_SECTORS: usize = 255;
+const EXTERNAL_FLAG: u8 = 0x80;
+
+fn compression_id(compression: Compression) -> Result<u8, NbtError> {
+    match compression {
+        Compression::Gzip => Ok(1),
+        Compression::Zlib => Ok(2),
+        Compression::None => Ok(3),
+        Compression::Lz4 => Ok(4),
+        _ => Err(NbtError::UnsupportedCompression(compression)),
+    }
+}
+
+fn compression_from_id(id: u8) -> Result<Compression, NbtError> {
+    match id {
+        1 => Ok(Compression::Gzip),
+        2 => Ok(Compression::Zlib),
+        3 => Ok(Compression::None),
+        4 => Ok(Compression::Lz4),
+        _ => Err(NbtError::UnknownCompression(id)),
+    }
+}
+
+fn sectors_for(len: usize) -> usize {
+    len.div_ceil(SECTOR_SIZE)
+}
+
+pub struct Region {
+    file: File,
+    dir: PathBuf,
+    locations: [u32; CHUNKS],
+    timestamps: [u32; CHUNKS],
+    used: Vec<bool>,
+    compression: Compression,
+}
+
+impl Region {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, NbtError> {
+        let path = path.as_ref();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut header = vec![0; SECTOR_SIZE * HEADER_SECTORS];
+
+        if file.metadata()?.len() < header.len() as u64 {
+            // new files start with an empty header
+            file.write_all(&header)?;
+        } else {
+            file.read_exact(&mut header)?;
+        }
+
+        let mut locations = [0; CHUNKS];
+        let mut timestamps = [0; CHUNKS];
+
+        for i in 0..CHUNKS {
+            let location = &header[i * 4..i * 4 + 4];
+            let timestamp = &header[SECTOR_SIZE + i * 4..SECTOR_SIZE + i * 4 + 4];
+
+            locations[i] = u32::from_be_bytes([location[0], location[1], location[2], location[3]]);
+            timestamps[i] = u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]);
+        }
+
+        let total = sectors_for(file.metadata()?.len() as usize).max(HEADER_SECTORS);
+        let mut used = vec![false; total];
+
+        for sector in used.iter_mut().take(HEADER_SECTORS) {
+            *sector = true;
+        }
+
+        for &location in locations.iter() {
+            let offset = (location >> 8) as usize;
+            let count = (location & 0xff) as usize;
+
+            // corrupt entries are ignored here and reported when the chunk is read
+            if offset >= HEADER_SECTORS && offset + count <= total {
+                for sector in &mut used[offset..offset + count] {
+                    *sector = true;
+                }
+            }
+        }
+
+        Ok(Self {
+            file,
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            locations,
+            timestamps,
+            used,
+            compression: Compression::Zlib,
+        })
+    }
+
+    pub fn file_name(region_x: i32, region_z: i32) -> String {
+        format!("r.{}.{}.mca", region_x, region_z)
+    }
+
+    // the compression used for chunks written from now on, zlib by default like vanilla
+    pub fn with_compression(mut self, compression: Compression) -> Result<Self, NbtError> {
+        compression_id(compression)?;
+        self.compression = compression;
+
+        Ok(self)
+    }
+
+    fn index(x: i32, z: i32) -> usize {
+        ((x & 31) + (z & 31) * 32) as usize
+    }
+
+    fn external_path(&self, x: i32, z: i32) -> PathBuf {
+        self.dir.join(format!("c.{}.{}.mcc", x, z))
+    }
+
+    pub fn has_chunk(&self, x: i32, z: i32) -> bool {
+        self.locations[Self::index(x, z)] != 0
+    }
+
+    pub fn timestamp(&self, x: i32, z: i32) -> u32 {
+        self.timestamps[Self::index(x, z)]
+    }
+
+    // chunk coordinates are absolute, only their low five bits select the entry in this region
+    pub fn read_chunk(&mut self, x: i32, z: i32) -> Result<Option<Nbt>, NbtError> {
+        match self.read_raw(x, z)? {
+            Some((data, compression)) => Nbt::decode_with(&mut &data[..], compression).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    // the uncompressed nbt of a chunk, to scan with NbtView instead of decoding all of it
+    pub fn read_chunk_data(&mut self, x: i32, z: i32) -> Result<Option<Vec<u8>>, NbtError> {
+        match self.read_raw(x, z)? {
+            Some((data, compression)) => decompress(&data, compression).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read_raw(&mut self, x: i32, z: i32) -> Result<Option<(Vec<u8>, Compression)>, NbtError> {
+        let location = self.locations[Self::index(x, z)];
+
+        if location == 0 {
+            return Ok(None);
+        }
+
+        let offset = (location >> 8) as usize;
+        let count = (location & 0xff) as usize;
+
+        if offset < HEADER_SECTORS || count == 0 || offset + count > self.used.len() {
+            return Err(NbtError::CorruptChunk(x, z));
+        }
+
+        let mut header = [0; 5];
+
+        self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.read_exact(&mut header)?;
+
+        let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let id = header[4];
+
+        // the length counts the compression byte and must fit in the allocated sectors
+        if len == 0 || len + 4 > count * SECTOR_SIZE {
+            return Err(NbtError::CorruptChunk(x, z));
+        }
+
+        let data = if id & EXTERNAL_FLAG != 0 {
+            fs::read(self.external_path(x, z))?
+        } else {
+            let mut data = vec![0; len - 1];
+
+            self.file.read_exact(&mut data)?;
+            data
+        };
+
+        let compression = compression_from_id(id & !EXTERNAL_FLAG)?;
+
+        Ok(Some((data, compression)))
+    }
+
+    pub fn write_chunk(&mut self, x: i32, z: i32, nbt: &Nbt) -> Result<(), NbtError> {
+        let id = compression_id(self.compression)?;
+        let mut data = vec![];
+
+        nbt.encode(&mut data, self.compression)?;
+
+        let external_path = self.external_path(x, z);
+        let (payload, id) = if sectors_for(data.len() + 5) > MAX_SECTORS {
+            fs::write(&external_path, &data)?;
+
+            (vec![], id | EXTERNAL_FLAG)
+        } else {
+            Self::remove_file(&external_path)?;
+
+            (data, id)
+        };
+
+        let i = Self::index(x, z);
+        let needed = sectors_for(payload.len() + 5);
+        let offset = self.allocate(i, needed);
+
+        let mut bytes = Vec::with_capacity(needed * SECTOR_SIZE);
+
+        bytes.extend_from_slice(&(payload.len() as u32 + 1).to_be_bytes());
+        bytes.push(id);
+        bytes.extend_from_slice(&payload);
+        bytes.resize(needed * SECTOR_SIZE, 0);
+
+        self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&bytes)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as u32)
+            .unwrap_or(0);
+
+        self.set_header(i, ((offset as u32) << 8) | needed as u32, timestamp)
+    }
+
+    pub fn remove_chunk(&mut self, x: i32, z: i32) -> Result<(), NbtError> {
+        let i = Self::index(x, z);
+
+        self.free(i);
+        Self::remove_file(&self.external_path(x, z))?;
+        self.set_header(i, 0, 0)
+    }
+
+    // finds sectors for a chunk, reusing its current ones in place when they are big enough
+    fn allocate(&mut self, i: usize, needed: usize) -> usize {
+        let location = self.locations[i];
+        let offset = (location >> 8) as usize;
+        let count = (location & 0xff) as usize;
+
+        if offset >= HEADER_SECTORS && count >= needed && offset + count <= self.used.len() {
+            for sector in &mut self.used[offset + needed..offset + count] {
+                *sector = false;
+            }
+
+            return offset;
+        }
+
+        self.free(i);
+
+        let mut start = HEADER_SECTORS;
+        let mut run = 0;
+
+        for sector in HEADER_SECTORS..self.used.len() {
+            if self.used[sector] {
+                start = sector + 1;
+                run = 0;
+            } else {
+                run += 1;
+
+                if run == needed {
+                    break;
+                }
+            }
+        }
+
+        // grow the file when no gap is large enough, using any free sectors at the end
+        if start + needed > self.used.len() {
+            self.used.resize(start + needed, false);
+        }
+
+        for sector in &mut self.used[start..start + needed] {
+            *sector = true;
+        }
+
+        start
+    }
+
+    fn free(&mut self, i: usize) {
+        let location = self.locations[i];
+        let offset = (location >> 8) as usize;
+        let count = (location & 0xff) as usize;
+
+        if offset >= HEADER_SECTORS && offset + count <= self.used.len() {
+            for sector in &mut self.used[offset..offset + count] {
+                *sector = false;
+            }
+        }
+    }
+
+    fn set_header(&mut self, i: usize, location: u32, timestamp: u32) -> Result<(), NbtError> {
+        self.locations[i] = location;
+        self.timestamps[i] = timestamp;
+
+        self.file.seek(SeekFrom::Start((i * 4) as u64))?;
+        self.file.write_all(&location.to_be_bytes())?;
+        self.file.seek(SeekFrom::Start((SECTOR_SIZE + i * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+
+        Ok(())
+    }
+
+    fn remove_file(path: &Path) -> Result<(), NbtError> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{Tag, Map, PathSegment, SchemaError, SchemaViolation};
+use blocky_core::ResourceLocation;
+
+// the shape a tag is expected to have, checked with validate
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    Any,
+    Byte,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    // a byte that is 0 or 1
+    Bool,
+    String,
+    // a string holding a namespaced id like minecraft:stone
+    ResourceLocation,
+    ByteArray,
+    IntArray,
+    LongArray,
+    List(Box<Schema>),
+    Compound(CompoundSchema),
+    OneOf(Vec<Schema>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    schema: Schema,
+    required: bool,
+}
+
+// unknown keys are allowed unless denied, since vanilla and mods add their own. fields are
+// checked in the order they were declared, so errors come out in that order too
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CompoundSchema {
+    fields: Vec<(String, Field)>,
+    deny_unknown: bool,
+}
+
+impl CompoundSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn required<S: Into<String>>(self, name: S, schema: Schema) -> Self {
+        self.with_field(name.into(), Field { schema, required: true })
+    }
+
+    pub fn optional<S: Into<String>>(self, name: S, schema: Schema) -> Self {
+        self.with_field(name.into(), Field { schema, required: false })
+    }
+
+    // declaring a field again replaces it where it was first declared
+    fn with_field(mut self, name: String, field: Field) -> Self {
+        match self.fields.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, f)) => *f = field,
+            None => self.fields.push((name, field)),
+        }
+
+        self
+    }
+
+    pub fn deny_unknown(mut self) -> Self {
+        self.deny_unknown = true;
+        self
+    }
+
+    fn check(&self, m: &Map<String, Tag>, path: &mut Vec<PathSegment>, errors: &mut Vec<SchemaError>) {
+        for (name, field) in &self.fields {
+            path.push(PathSegment::Key(name.clone()));
+
+            // end tags are never written, so they count as missing
+            match m.get(name) {
+                Some(tag) if *tag != Tag::End => field.schema.check(tag, path, errors),
+                _ if field.required => errors.push(SchemaError {
+                    path: path.clone(),
+                    violation: SchemaViolation::Missing,
+                }),
+                _ => {},
+            }
+
+            path.pop();
+        }
+
+        if self.deny_unknown {
+            let unknown = m.iter()
+                .filter(|(_, tag)| **tag != Tag::End)
+                .map(|(name, _)| name)
+                .filter(|name| !self.fields.iter().any(|(n, _)| n == *name));
+
+            for name in unknown {
+                let mut path = path.clone();
+
+                path.push(PathSegment::Key(name.clone()));
+                errors.push(SchemaError { path, violation: SchemaViolation::Unknown });
+            }
+        }
+    }
+}
+
+impl Schema {
+    pub fn list(element: Schema) -> Self {
+        Self::List(Box::new(element))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Any => "any tag",
+            Self::Byte => "byte",
+            Self::Short => "short",
+            Self::Int => "int",
+            Self::Long => "long",
+            Self::Float => "float",
+            Self::Double => "double",
+            Self::Bool => "boolean",
+            Self::String => "string",
+            Self::ResourceLocation => "resource location string",
+            Self::ByteArray => "byte array",
+            Self::IntArray => "int array",
+            Self::LongArray => "long array",
+            Self::List(_) => "list",
+            Self::Compound(_) => "compound",
+            Self::OneOf(_) => "one of several types",
+        }
+    }
+
+    // every mismatch found, each with the path to it, where an empty result means the tag is valid
+    pub fn validate(&self, tag: &Tag) -> Vec<SchemaError> {
+        let mut errors = vec![];
+
+        self.check(tag, &mut vec![], &mut errors);
+
+        errors
+    }
+
+    pub fn is_valid(&self, tag: &Tag) -> bool {
+        self.validate(tag).is_empty()
+    }
+
+    fn check(&self, tag: &Tag, path: &mut Vec<PathSegment>, errors: &mut Vec<SchemaError>) {
+        let violation = match (self, tag) {
+            (Self::Any, _)
+            | (Self::Byte, Tag::Byte(_))
+            | (Self::Short, Tag::Short(_))
+            | (Self::Int, Tag::Int(_))
+            | (Self::Long, Tag::Long(_))
+            | (Self::Float, Tag::Float(_))
+            | (Self::Double, Tag::Double(_))
+            | (Self::Bool, Tag::Byte(0 | 1))
+            | (Self::String, Tag::String(_))
+            | (Self::ByteArray, Tag::ByteArray(_))
+            | (Self::IntArray, Tag::IntArray(_))
+            | (Self::LongArray, Tag::LongArray(_)) => return,
+
+            (Self::Bool, Tag::Byte(value)) => SchemaViolation::NotBool(*value),
+            (Self::ResourceLocation, Tag::String(s)) => match s.parse::<ResourceLocation>() {
+                Ok(_) => return,
+                Err(e) => SchemaViolation::InvalidResourceLocation(s.clone(), e.to_string()),
+            },
+
+            (Self::List(element), Tag::List(v)) => {
+                for (i, tag) in v.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    element.check(tag, path, errors);
+                    path.pop();
+                }
+
+                return;
+            },
+            (Self::Compound(schema), Tag::Compound(m)) => return schema.check(m, path, errors),
+            (Self::OneOf(schemas), tag) if schemas.iter().any(|schema| schema.is_valid(tag)) => return,
+            (Self::OneOf(_), _) => SchemaViolation::NoMatch,
+
+            (schema, tag) => SchemaViolation::WrongType {
+                expected: schema.name(),
+                found: tag.kind(),
+            },
+        };
+
+        errors.push(SchemaError { path: path.clone(), violation });
+    }
+}
+
+impl From<CompoundSchema> for Schema {
+    fn from(schema: CompoundSchema) -> Self {
+        Self::Compound(schema)
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{Nbt, Tag, Kind, Map, Compression, NbtError};
+use crate::array::{BYTE_ARRAY, INT_ARRAY, LONG_ARRAY};
+use serde::ser::{self, Serialize};
+use std::io::Write;
+
+pub fn to_tag<T: ?Sized + Serialize>(value: &T) -> Result<Tag, NbtError> {
+    value.serialize(Serializer)
+}
+
+// writes a nameless root in the format Nbt::encode produces
+pub fn to_writer<W, T>(dst: &mut W, value: &T, compression: Compression) -> Result<(), NbtError>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let nbt = Nbt::new(String::new(), to_tag(value)?);
+
+    nbt.encode(dst, compression)
+}
+
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeVariant<SerializeCompound>;
+
+    fn serialize_bool(self, v: bool) -> Result<Tag, NbtError> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Tag, NbtError> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Tag, NbtError> {
+        Ok(Tag::Short(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Tag, NbtError> {
+        Ok(Tag::Int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Tag, NbtError> {
+        Ok(Tag::Long(v))
+    }
+
+    // nbt has no unsigned types, so store the bits in the signed tag of the same width
+
+    fn serialize_u8(self, v: u8) -> Result<Tag, NbtError> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Tag, NbtError> {
+        Ok(Tag::Short(v as i16))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Tag, NbtError> {
+        Ok(Tag::Int(v as i32))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Tag, NbtError> {
+        Ok(Tag::Long(v as i64))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Tag, NbtError> {
+        Ok(Tag::Float(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Tag, NbtError> {
+        Ok(Tag::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Tag, NbtError> {
+        Ok(Tag::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Tag, NbtError> {
+        Ok(Tag::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Tag, NbtError> {
+        Ok(Tag::ByteArray(v.iter().map(|b| *b as i8).collect()))
+    }
+
+    // none is stored as end, which compounds skip when encoding
+    fn serialize_none(self) -> Result<Tag, NbtError> {
+        Ok(Tag::End)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Tag, NbtError> {
+        value.serialize(self)
+    }
+
+    // nbt has no empty value, and returning end would make a struct drop the field silently
+    fn serialize_unit(self) -> Result<Tag, NbtError> {
+        Err(NbtError::UnsupportedType("unit"))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Tag, NbtError> {
+        Ok(Tag::Compound(Map::new()))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Tag, NbtError> {
+        Ok(Tag::String(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Tag, NbtError> {
+        let tag = value.serialize(self)?;
+
+        match name {
+            BYTE_ARRAY => into_array(tag, "byte", |tag| match tag {
+                Tag::Byte(value) => Some(value),
+                _ => None,
+            }).map(Tag::ByteArray),
+            INT_ARRAY => into_array(tag, "int", |tag| match tag {
+                Tag::Int(value) => Some(value),
+                _ => None,
+            }).map(Tag::IntArray),
+            LONG_ARRAY => into_array(tag, "long", |tag| match tag {
+                Tag::Long(value) => Some(value),
+                _ => None,
+            }).map(Tag::LongArray),
+            _ => Ok(tag),
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Tag, NbtError> {
+        let mut m = Map::new();
+
+        m.insert(variant.to_owned(), value.serialize(self)?);
+
+        Ok(Tag::Compound(m))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, NbtError> {
+        Ok(SerializeList {
+            kind: Kind::End,
+            v: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, NbtError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeList, NbtError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeList>, NbtError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeCompound, NbtError> {
+        Ok(SerializeCompound {
+            m: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeCompound, NbtError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeCompound>, NbtError> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+fn into_array<T, F>(tag: Tag, name: &'static str, f: F) -> Result<Vec<T>, NbtError>
+where
+    F: Fn(Tag) -> Option<T>,
+{
+    match tag {
+        Tag::List(v) => v.into_iter()
+            .map(|tag| f(tag).ok_or(NbtError::InvalidArray(name)))
+            .collect(),
+        _ => Err(NbtError::InvalidArray(name)),
+    }
+}
+
+pub struct SerializeList {
+    kind: Kind,
+    v: Vec<Tag>,
+}
+
+impl SerializeList {
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        let tag = value.serialize(Serializer)?;
+        let kind = tag.kind();
+
+        if kind == Kind::End {
+            return Err(NbtError::UnsupportedType("none in list"));
+        } else if self.v.is_empty() {
+            self.kind = kind;
+        } else if self.kind != kind {
+            return Err(NbtError::MixedList);
+        }
+
+        self.v.push(tag);
+
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Tag::List(self.v.into()))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Tag::List(self.v.into()))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Tag::List(self.v.into()))
+    }
+}
+
+pub struct SerializeCompound {
+    m: Map<String, Tag>,
+    key: Option<String>,
+}
+
+impl SerializeCompound {
+    fn insert<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<(), NbtError> {
+        let tag = value.serialize(Serializer)?;
+
+        // skip none values so optional fields are left out
+        if tag != Tag::End {
+            self.m.insert(key, tag);
+        }
+
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), NbtError> {
+        self.key = match key.serialize(Serializer)? {
+            Tag::String(key) => Some(key),
+            _ => return Err(NbtError::KeyMustBeString),
+        };
+
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        let key = self.key.take()
+            .ok_or_else(|| NbtError::Message(String::from("value serialized before key")))?;
+
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Tag::Compound(self.m))
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NbtError> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Tag::Compound(self.m))
+    }
+}
+
+pub struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, tag: Tag) -> Tag {
+        let mut m = Map::new();
+
+        m.insert(variant.to_owned(), tag);
+
+        Tag::Compound(m)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Self::wrap(self.variant, Tag::List(self.inner.v.into())))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeCompound> {
+    type Ok = Tag;
+    type Error = NbtError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NbtError> {
+        self.inner.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Tag, NbtError> {
+        Ok(Self::wrap(self.variant, Tag::Compound(self.inner.m)))
+    }
+}
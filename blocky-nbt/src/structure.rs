@@ -0,0 +1,375 @@
+use crate::{Nbt, Tag, Map, List, Kind, Compression, NbtError, BlockState, Rotation, Mirror};
+use crate::__private::take;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+// a block placed at a position relative to the structure's origin, with an index into the
+// palettes and the block entity data if it has any
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureBlock {
+    pub pos: [i32; 3],
+    pub state: usize,
+    pub nbt: Option<Map<String, Tag>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructureEntity {
+    pub pos: [f64; 3],
+    pub block_pos: [i32; 3],
+    pub nbt: Map<String, Tag>,
+}
+
+// the layout used by structure blocks and jigsaw pieces. files with several palettes, like
+// shipwrecks, hold one variant per palette and every block indexes into all of them
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Structure {
+    pub data_version: Option<i32>,
+    pub size: [i32; 3],
+    pub palettes: Vec<Vec<BlockState>>,
+    pub blocks: Vec<StructureBlock>,
+    pub entities: Vec<StructureEntity>,
+    // keys this model doesn't know about, written back unchanged
+    pub extra: Map<String, Tag>,
+}
+
+fn field(m: &mut Map<String, Tag>, name: &'static str) -> Result<Tag, NbtError> {
+    take(m, name).ok_or(NbtError::MissingField(name))
+}
+
+fn compound(tag: Tag) -> Result<Map<String, Tag>, NbtError> {
+    Map::try_from(tag)
+}
+
+fn list(tag: Tag) -> Result<List, NbtError> {
+    List::try_from(tag)
+}
+
+fn ints(tag: &Tag) -> Result<[i32; 3], NbtError> {
+    match tag.as_list().map(|v| &v[..]) {
+        Some([Tag::Int(x), Tag::Int(y), Tag::Int(z)]) => Ok([*x, *y, *z]),
+        Some(_) => Err(NbtError::InvalidArray("3 int")),
+        None => Err(tag.wrong_type("list")),
+    }
+}
+
+fn doubles(tag: &Tag) -> Result<[f64; 3], NbtError> {
+    match tag.as_list().map(|v| &v[..]) {
+        Some([Tag::Double(x), Tag::Double(y), Tag::Double(z)]) => Ok([*x, *y, *z]),
+        Some(_) => Err(NbtError::InvalidArray("3 double")),
+        None => Err(tag.wrong_type("list")),
+    }
+}
+
+fn compounds(tags: Vec<Tag>) -> Tag {
+    Tag::List(List::from_tags_unchecked(Kind::Compound.id(), tags))
+}
+
+fn palette(tag: Tag) -> Result<Vec<BlockState>, NbtError> {
+    list(tag)?.iter().map(BlockState::from_tag).collect()
+}
+
+// moves a position within a box of the given size, where size is one more than the largest
+// block coordinate and the largest entity coordinate
+fn rotate_pos<T>(pos: [T; 3], size: [T; 3], rotation: Rotation) -> [T; 3]
+where
+    T: Copy + std::ops::Sub<Output = T>,
+{
+    let [x, y, z] = pos;
+    let [sx, _, sz] = size;
+
+    match rotation {
+        Rotation::None => pos,
+        Rotation::Clockwise90 => [sz - z, y, x],
+        Rotation::Clockwise180 => [sx - x, y, sz - z],
+        Rotation::Counterclockwise90 => [z, y, sx - x],
+    }
+}
+
+fn mirror_pos<T>(pos: [T; 3], size: [T; 3], mirror: Mirror) -> [T; 3]
+where
+    T: Copy + std::ops::Sub<Output = T>,
+{
+    let [x, y, z] = pos;
+    let [sx, _, sz] = size;
+
+    match mirror {
+        Mirror::None => pos,
+        Mirror::LeftRight => [x, y, sz - z],
+        Mirror::FrontBack => [sx - x, y, z],
+    }
+}
+
+fn block_bounds(size: [i32; 3]) -> [i32; 3] {
+    [size[0] - 1, size[1] - 1, size[2] - 1]
+}
+
+fn entity_bounds(size: [i32; 3]) -> [f64; 3] {
+    [size[0] as f64, size[1] as f64, size[2] as f64]
+}
+
+// turns the yaw in an entity's Rotation list like vanilla does when placing a structure
+fn turn_entity<F: Fn(f32) -> f32>(nbt: &mut Map<String, Tag>, f: F) {
+    if let Some(Tag::List(v)) = nbt.get_mut("Rotation") {
+        if let Some(Tag::Float(yaw)) = v.first_mut() {
+            *yaw = f(*yaw);
+        }
+    }
+}
+
+impl StructureBlock {
+    // palette_len is the length of the shortest palette, which every state has to index into
+    fn from_tag(tag: Tag, palette_len: usize) -> Result<Self, NbtError> {
+        let mut m = compound(tag)?;
+        let pos = ints(&field(&mut m, "pos")?)?;
+        let state = i32::try_from(field(&mut m, "state")?)?;
+        let nbt = take(&mut m, "nbt").map(compound).transpose()?;
+
+        let state = match usize::try_from(state) {
+            Ok(state) if state < palette_len => state,
+            _ => return Err(NbtError::InvalidPalette(state as i64, palette_len)),
+        };
+
+        Ok(Self { pos, state, nbt })
+    }
+
+    fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("pos".to_owned(), Tag::from(self.pos.iter().map(|v| Tag::Int(*v)).collect::<Vec<_>>()));
+        m.insert("state".to_owned(), Tag::Int(self.state as i32));
+
+        if let Some(nbt) = &self.nbt {
+            m.insert("nbt".to_owned(), Tag::Compound(nbt.clone()));
+        }
+
+        Tag::Compound(m)
+    }
+}
+
+impl StructureEntity {
+    fn from_tag(tag: Tag) -> Result<Self, NbtError> {
+        let mut m = compound(tag)?;
+
+        Ok(Self {
+            pos: doubles(&field(&mut m, "pos")?)?,
+            block_pos: ints(&field(&mut m, "blockPos")?)?,
+            nbt: compound(field(&mut m, "nbt")?)?,
+        })
+    }
+
+    fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("pos".to_owned(), Tag::from(self.pos.iter().map(|v| Tag::Double(*v)).collect::<Vec<_>>()));
+        m.insert("blockPos".to_owned(), Tag::from(self.block_pos.iter().map(|v| Tag::Int(*v)).collect::<Vec<_>>()));
+        m.insert("nbt".to_owned(), Tag::Compound(self.nbt.clone()));
+
+        Tag::Compound(m)
+    }
+}
+
+impl Structure {
+    // an empty structure with a single palette
+    pub fn new(size: [i32; 3]) -> Self {
+        Self {
+            size,
+            palettes: vec![vec![]],
+            ..Self::default()
+        }
+    }
+
+    pub fn from_tag(tag: Tag) -> Result<Self, NbtError> {
+        let mut m = compound(tag)?;
+
+        let data_version = take(&mut m, "DataVersion").map(i32::try_from).transpose()?;
+        let size = ints(&field(&mut m, "size")?)?;
+
+        let palettes: Vec<Vec<BlockState>> = match (take(&mut m, "palette"), take(&mut m, "palettes")) {
+            (Some(tag), _) => vec![palette(tag)?],
+            (None, Some(tag)) => list(tag)?
+                .into_iter()
+                .map(palette)
+                .collect::<Result<_, _>>()?,
+            (None, None) => return Err(NbtError::MissingField("palette")),
+        };
+
+        let palette_len = palettes.iter().map(Vec::len).min().unwrap_or(0);
+        let blocks = list(field(&mut m, "blocks")?)?
+            .into_iter()
+            .map(|tag| StructureBlock::from_tag(tag, palette_len))
+            .collect::<Result<_, _>>()?;
+
+        let entities = match take(&mut m, "entities") {
+            Some(tag) => list(tag)?
+                .into_iter()
+                .map(StructureEntity::from_tag)
+                .collect::<Result<_, _>>()?,
+            None => vec![],
+        };
+
+        Ok(Self {
+            data_version,
+            size,
+            palettes,
+            blocks,
+            entities,
+            extra: m,
+        })
+    }
+
+    // writes the keys in the order vanilla does
+    pub fn to_tag(&self) -> Tag {
+        let mut m = Map::new();
+
+        m.insert("blocks".to_owned(), compounds(self.blocks.iter().map(StructureBlock::to_tag).collect()));
+
+        let palette = |states: &Vec<BlockState>| compounds(states.iter().map(BlockState::to_tag).collect());
+
+        match &self.palettes[..] {
+            [] => {
+                m.insert("palette".to_owned(), compounds(vec![]));
+            },
+            [states] => {
+                m.insert("palette".to_owned(), palette(states));
+            },
+            palettes => {
+                let palettes = palettes.iter().map(palette).collect();
+
+                m.insert("palettes".to_owned(), Tag::List(List::from_tags_unchecked(Kind::List(0).id(), palettes)));
+            },
+        }
+
+        m.insert("entities".to_owned(), compounds(self.entities.iter().map(StructureEntity::to_tag).collect()));
+        m.insert("size".to_owned(), Tag::from(self.size.iter().map(|v| Tag::Int(*v)).collect::<Vec<_>>()));
+
+        if let Some(version) = self.data_version {
+            m.insert("DataVersion".to_owned(), Tag::Int(version));
+        }
+
+        for (key, value) in &self.extra {
+            m.insert(key.clone(), value.clone());
+        }
+
+        Tag::Compound(m)
+    }
+
+    pub fn from_nbt(nbt: Nbt) -> Result<Self, NbtError> {
+        Self::from_tag(nbt.tag)
+    }
+
+    pub fn to_nbt(&self) -> Nbt {
+        Nbt::new(String::new(), self.to_tag())
+    }
+
+    pub fn load<R: Read>(src: &mut R) -> Result<Self, NbtError> {
+        Self::from_nbt(Nbt::decode(src)?)
+    }
+
+    // structure files are always gzipped
+    pub fn save<W: Write>(&self, dst: &mut W) -> Result<(), NbtError> {
+        self.to_nbt().encode(dst, Compression::Gzip)
+    }
+
+    pub fn block_at(&self, pos: [i32; 3]) -> Option<&StructureBlock> {
+        self.blocks.iter().find(|block| block.pos == pos)
+    }
+
+    // the state from the first palette
+    pub fn state_at(&self, pos: [i32; 3]) -> Option<&BlockState> {
+        let block = self.block_at(pos)?;
+
+        self.palettes.first()?.get(block.state)
+    }
+
+    // replaces any block already at the position. an index is only reused where every palette
+    // holds the state, otherwise the state is added to each of them, so every variant gets the
+    // same block
+    pub fn set_block(&mut self, pos: [i32; 3], state: BlockState, nbt: Option<Map<String, Tag>>) {
+        if self.palettes.is_empty() {
+            self.palettes.push(vec![]);
+        }
+
+        let shared = (0..self.palettes[0].len())
+            .find(|i| self.palettes.iter().all(|palette| palette.get(*i) == Some(&state)));
+
+        let index = match shared {
+            Some(index) => index,
+            None => {
+                for palette in &mut self.palettes {
+                    palette.push(state.clone());
+                }
+
+                self.palettes[0].len() - 1
+            },
+        };
+
+        let block = StructureBlock { pos, state: index, nbt };
+
+        match self.blocks.iter_mut().find(|block| block.pos == pos) {
+            Some(existing) => *existing = block,
+            None => self.blocks.push(block),
+        }
+    }
+
+    // leaves the state in the palettes, since other blocks may still use it
+    pub fn remove_block(&mut self, pos: [i32; 3]) -> Option<StructureBlock> {
+        let index = self.blocks.iter().position(|block| block.pos == pos)?;
+
+        Some(self.blocks.remove(index))
+    }
+
+    // turns the structure around the vertical axis, keeping it at the same origin
+    pub fn rotate(&mut self, rotation: Rotation) {
+        let blocks = block_bounds(self.size);
+        let entities = entity_bounds(self.size);
+
+        for palette in &mut self.palettes {
+            for state in palette {
+                state.rotate(rotation);
+            }
+        }
+
+        for block in &mut self.blocks {
+            block.pos = rotate_pos(block.pos, blocks, rotation);
+        }
+
+        for entity in &mut self.entities {
+            entity.pos = rotate_pos(entity.pos, entities, rotation);
+            entity.block_pos = rotate_pos(entity.block_pos, blocks, rotation);
+
+            let turns = rotation.turns() as f32;
+
+            turn_entity(&mut entity.nbt, |yaw| yaw + turns * 90.0);
+        }
+
+        if rotation.turns() % 2 == 1 {
+            self.size.swap(0, 2);
+        }
+    }
+
+    pub fn mirror(&mut self, mirror: Mirror) {
+        let blocks = block_bounds(self.size);
+        let entities = entity_bounds(self.size);
+
+        for palette in &mut self.palettes {
+            for state in palette {
+                state.mirror(mirror);
+            }
+        }
+
+        for block in &mut self.blocks {
+            block.pos = mirror_pos(block.pos, blocks, mirror);
+        }
+
+        for entity in &mut self.entities {
+            entity.pos = mirror_pos(entity.pos, entities, mirror);
+            entity.block_pos = mirror_pos(entity.block_pos, blocks, mirror);
+
+            match mirror {
+                Mirror::None => {},
+                Mirror::LeftRight => turn_entity(&mut entity.nbt, |yaw| 180.0 - yaw),
+                Mirror::FrontBack => turn_entity(&mut entity.nbt, |yaw| -yaw),
+            }
+        }
+    }
+}
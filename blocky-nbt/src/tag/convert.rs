@@ -0,0 +1,301 @@
+use super::{Tag, List, Map};
+use crate::NbtError;
+use std::convert::TryFrom;
+
+macro_rules! from_value {
+    { $({ $input:ty, $output:ident }),* $(,)* } => {
+        $(
+            impl From<$input> for Tag {
+                fn from(value: $input) -> Self {
+                    Tag::$output(value)
+                }
+            }
+        )*
+    };
+}
+
+// numbers convert from their own tag and any smaller one that fits without loss
+macro_rules! try_from_number {
+    ($typ:ty, $name:expr, $($kind:ident),*) => {
+        impl TryFrom<&Tag> for $typ {
+            type Error = NbtError;
+
+            fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+                match tag {
+                    $(Tag::$kind(value) => Ok(<$typ>::from(*value)),)*
+                    _ => Err(tag.wrong_type($name)),
+                }
+            }
+        }
+
+        impl TryFrom<Tag> for $typ {
+            type Error = NbtError;
+
+            fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+                Self::try_from(&tag)
+            }
+        }
+    };
+}
+
+macro_rules! try_from_owned {
+    ($typ:ty, $kind:ident, $name:expr) => {
+        impl TryFrom<&Tag> for $typ {
+            type Error = NbtError;
+
+            fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+                match tag {
+                    Tag::$kind(value) => Ok(value.clone()),
+                    _ => Err(tag.wrong_type($name)),
+                }
+            }
+        }
+
+        impl TryFrom<Tag> for $typ {
+            type Error = NbtError;
+
+            fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+                match tag {
+                    Tag::$kind(value) => Ok(value),
+                    tag => Err(tag.wrong_type($name)),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! as_ref {
+    ($name:ident, $name_mut:ident, $kind:ident, $typ:ty) => {
+        pub fn $name(&self) -> Option<&$typ> {
+            match self {
+                Tag::$kind(value) => Some(value),
+                _ => None,
+            }
+        }
+
+        pub fn $name_mut(&mut self) -> Option<&mut $typ> {
+            match self {
+                Tag::$kind(value) => Some(value),
+                _ => None,
+            }
+        }
+    };
+}
+
+from_value! {
+    { i8, Byte },
+    { i16, Short },
+    { i32, Int },
+    { i64, Long },
+
+    { f32, Float },
+    { f64, Double },
+
+    { String, String },
+
+    { Vec<i8>, ByteArray },
+    { Vec<i32>, IntArray },
+    { Vec<i64>, LongArray },
+
+    { List, List },
+    { Map<String, Tag>, Compound },
+}
+
+impl From<Vec<Tag>> for Tag {
+    fn from(value: Vec<Tag>) -> Self {
+        Tag::List(value.into())
+    }
+}
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::Byte(value as i8)
+    }
+}
+
+impl From<&str> for Tag {
+    fn from(value: &str) -> Self {
+        Tag::String(value.to_owned())
+    }
+}
+
+try_from_number!(i8, "byte", Byte);
+try_from_number!(i16, "short", Byte, Short);
+try_from_number!(i32, "int", Byte, Short, Int);
+try_from_number!(i64, "long", Byte, Short, Int, Long);
+try_from_number!(f32, "float", Byte, Short, Float);
+try_from_number!(f64, "double", Byte, Short, Int, Float, Double);
+
+try_from_owned!(String, String, "string");
+try_from_owned!(Vec<i8>, ByteArray, "byte array");
+try_from_owned!(Vec<i32>, IntArray, "int array");
+try_from_owned!(Vec<i64>, LongArray, "long array");
+try_from_owned!(List, List, "list");
+try_from_owned!(Map<String, Tag>, Compound, "compound");
+
+impl TryFrom<&Tag> for Vec<Tag> {
+    type Error = NbtError;
+
+    fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+        List::try_from(tag).map(List::into_vec)
+    }
+}
+
+impl TryFrom<Tag> for Vec<Tag> {
+    type Error = NbtError;
+
+    fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+        List::try_from(tag).map(List::into_vec)
+    }
+}
+
+// only 0b and 1b, where as_bool accepts any number like vanilla
+impl TryFrom<&Tag> for bool {
+    type Error = NbtError;
+
+    fn try_from(tag: &Tag) -> Result<Self, Self::Error> {
+        match tag {
+            Tag::Byte(0) => Ok(false),
+            Tag::Byte(1) => Ok(true),
+            _ => Err(tag.wrong_type("boolean")),
+        }
+    }
+}
+
+impl TryFrom<Tag> for bool {
+    type Error = NbtError;
+
+    fn try_from(tag: Tag) -> Result<Self, Self::Error> {
+        Self::try_from(&tag)
+    }
+}
+
+impl<'a> TryFrom<&'a Tag> for &'a str {
+    type Error = NbtError;
+
+    fn try_from(tag: &'a Tag) -> Result<Self, Self::Error> {
+        tag.as_str().ok_or_else(|| tag.wrong_type("string"))
+    }
+}
+
+// the value of a numeric tag, owned or in a view, converted the same way by both like
+// vanilla's getAsLong, getAsInt and so on, truncating wider integers and flooring floats
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Number {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+impl Number {
+    pub(crate) fn as_i32(self) -> i32 {
+        match self {
+            Self::Byte(value) => value as i32,
+            Self::Short(value) => value as i32,
+            Self::Int(value) => value,
+            Self::Long(value) => value as i32,
+            Self::Float(value) => value.floor() as i32,
+            Self::Double(value) => value.floor() as i32,
+        }
+    }
+
+    pub(crate) fn as_i64(self) -> i64 {
+        match self {
+            Self::Long(value) => value,
+            // vanilla truncates floats here but floors doubles
+            Self::Float(value) => value as i64,
+            Self::Double(value) => value.floor() as i64,
+            _ => self.as_i32() as i64,
+        }
+    }
+
+    // converts each type directly like getAsFloat, since going through a double rounds longs twice
+    pub(crate) fn as_f32(self) -> f32 {
+        match self {
+            Self::Byte(value) => value as f32,
+            Self::Short(value) => value as f32,
+            Self::Int(value) => value as f32,
+            Self::Long(value) => value as f32,
+            Self::Float(value) => value,
+            Self::Double(value) => value as f32,
+        }
+    }
+
+    pub(crate) fn as_f64(self) -> f64 {
+        match self {
+            Self::Byte(value) => value as f64,
+            Self::Short(value) => value as f64,
+            Self::Int(value) => value as f64,
+            Self::Long(value) => value as f64,
+            Self::Float(value) => value as f64,
+            Self::Double(value) => value,
+        }
+    }
+}
+
+// the as_ number methods accept any numeric tag, converted as Number describes
+impl Tag {
+    pub(crate) fn wrong_type(&self, expected: &'static str) -> NbtError {
+        NbtError::WrongType {
+            expected,
+            found: self.kind(),
+        }
+    }
+
+    fn number(&self) -> Option<Number> {
+        match *self {
+            Tag::Byte(value) => Some(Number::Byte(value)),
+            Tag::Short(value) => Some(Number::Short(value)),
+            Tag::Int(value) => Some(Number::Int(value)),
+            Tag::Long(value) => Some(Number::Long(value)),
+            Tag::Float(value) => Some(Number::Float(value)),
+            Tag::Double(value) => Some(Number::Double(value)),
+            _ => None,
+        }
+    }
+
+    pub fn as_i8(&self) -> Option<i8> {
+        self.as_i32().map(|value| value as i8)
+    }
+
+    pub fn as_i16(&self) -> Option<i16> {
+        self.as_i32().map(|value| value as i16)
+    }
+
+    pub fn as_i32(&self) -> Option<i32> {
+        self.number().map(Number::as_i32)
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.number().map(Number::as_i64)
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        self.number().map(Number::as_f32)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.number().map(Number::as_f64)
+    }
+
+    // like vanilla's getBoolean, true for any number that isn't zero as a byte
+    pub fn as_bool(&self) -> Option<bool> {
+        self.as_i8().map(|value| value != 0)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    as_ref!(as_list, as_list_mut, List, List);
+    as_ref!(as_compound, as_compound_mut, Compound, Map<String, Tag>);
+    as_ref!(as_byte_array, as_byte_array_mut, ByteArray, Vec<i8>);
+    as_ref!(as_int_array, as_int_array_mut, IntArray, Vec<i32>);
+    as_ref!(as_long_array, as_long_array_mut, LongArray, Vec<i64>);
+}
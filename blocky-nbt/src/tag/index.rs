@@ -3,7 +3,8 @@ use super::Tag;
 pub trait Index {
     fn index_into<'a>(&self, tag: &'a Tag) -> Option<&'a Tag>;
     fn index_into_mut<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag>;
-    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> &'a mut Tag;
+    // a missing compound key is added as an end tag, which is skipped when encoding
+    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag>;
 }
 
 impl Index for str {
@@ -21,15 +22,10 @@ impl Index for str {
         }
     }
 
-    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> &'a mut Tag {
+    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag> {
         match tag {
-            Tag::Compound(m) => {
-                let key = self.to_owned();
-
-                m.entry(key.clone())
-                    .or_insert(Tag::End)
-            },
-            _ => panic!("cannot index tag type: {:?}", tag.kind()),
+            Tag::Compound(m) => Some(m.entry(self.to_owned()).or_insert(Tag::End)),
+            _ => None,
         }
     }
 }
@@ -43,7 +39,7 @@ impl Index for String {
         self[..].index_into_mut(tag)
     }
 
-    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> &'a mut Tag {
+    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag> {
         self[..].index_or_insert(tag)
     }
 }
@@ -63,20 +59,8 @@ impl Index for usize {
         }
     }
 
-    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> &'a mut Tag {
-        match tag {
-            Tag::List(v) => {
-                let len = v.len();
-
-                v.get_mut(*self)
-                    .unwrap_or_else(|| panic!(
-                        "cannot access index {} in array of length {}",
-                        self,
-                        len,
-                    ))
-            },
-            _ => panic!("cannot access index of tag type: {:?}", tag.kind()),
-        }
+    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag> {
+        self.index_into_mut(tag)
     }
 }
 
@@ -89,7 +73,7 @@ impl<'b, T: ?Sized + Index> Index for &'b T {
         (**self).index_into_mut(tag)
     }
 
-    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> &'a mut Tag {
+    fn index_or_insert<'a>(&self, tag: &'a mut Tag) -> Option<&'a mut Tag> {
         (**self).index_or_insert(tag)
     }
 }
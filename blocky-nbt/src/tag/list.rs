@@ -0,0 +1,175 @@
+use super::{Tag, Kind};
+use crate::NbtError;
+use std::iter::FromIterator;
+use std::ops;
+use std::slice;
+use std::vec;
+
+// the tags of a list along with the id they're declared to have, so an empty list keeps the
+// type it was read with. elements replaced through the mutable slice aren't checked until the
+// list is encoded
+#[derive(Debug, Clone, Default)]
+pub struct List {
+    id: u8,
+    tags: Vec<Tag>,
+}
+
+// the declared type of an empty list is only kept for encoding, so all empty lists are equal
+impl PartialEq for List {
+    fn eq(&self, other: &Self) -> bool {
+        self.tags == other.tags && (self.tags.is_empty() || self.id == other.id)
+    }
+}
+
+impl List {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // an empty list declared to hold tags of this kind
+    pub fn of(kind: Kind) -> Self {
+        Self {
+            id: kind.id(),
+            tags: vec![],
+        }
+    }
+
+    pub fn from_tags(id: u8, tags: Vec<Tag>) -> Result<Self, NbtError> {
+        let list = Self { id, tags };
+
+        list.validate()?;
+
+        Ok(list)
+    }
+
+    // for tags already known to match, like those just decoded
+    pub(crate) fn from_tags_unchecked(id: u8, tags: Vec<Tag>) -> Self {
+        Self { id, tags }
+    }
+
+    // the id of the elements, which is the end id for an untyped empty list
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn validate(&self) -> Result<(), NbtError> {
+        if self.tags.iter().all(|tag| tag.kind().id() == self.id) {
+            Ok(())
+        } else {
+            Err(NbtError::MixedList)
+        }
+    }
+
+    // an untyped empty list takes the kind of the first tag added
+    fn check(&mut self, tag: &Tag) -> Result<(), NbtError> {
+        let id = tag.kind().id();
+
+        if self.tags.is_empty() && self.id == Kind::End.id() {
+            self.id = id;
+        }
+
+        if id != self.id || id == Kind::End.id() {
+            return Err(NbtError::MixedList);
+        }
+
+        Ok(())
+    }
+
+    pub fn push(&mut self, tag: Tag) -> Result<(), NbtError> {
+        self.check(&tag)?;
+        self.tags.push(tag);
+
+        Ok(())
+    }
+
+    // panics if the index is past the end, like Vec::insert
+    pub fn insert(&mut self, index: usize, tag: Tag) -> Result<(), NbtError> {
+        self.check(&tag)?;
+        self.tags.insert(index, tag);
+
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<Tag> {
+        self.tags.pop()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Tag {
+        self.tags.remove(index)
+    }
+
+    pub fn retain<F: FnMut(&Tag) -> bool>(&mut self, f: F) {
+        self.tags.retain(f);
+    }
+
+    // keeps the declared kind
+    pub fn clear(&mut self) {
+        self.tags.clear();
+    }
+
+    pub fn into_vec(self) -> Vec<Tag> {
+        self.tags
+    }
+}
+
+impl ops::Deref for List {
+    type Target = [Tag];
+
+    fn deref(&self) -> &Self::Target {
+        &self.tags
+    }
+}
+
+impl ops::DerefMut for List {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tags
+    }
+}
+
+// takes its kind from the first tag, leaving a mixed list to fail when encoded
+impl From<Vec<Tag>> for List {
+    fn from(tags: Vec<Tag>) -> Self {
+        let id = tags.first().map(|tag| tag.kind().id()).unwrap_or(Kind::End.id());
+
+        Self { id, tags }
+    }
+}
+
+impl From<List> for Vec<Tag> {
+    fn from(list: List) -> Self {
+        list.tags
+    }
+}
+
+impl FromIterator<Tag> for List {
+    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl IntoIterator for List {
+    type Item = Tag;
+    type IntoIter = vec::IntoIter<Tag>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tags.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = &'a Tag;
+    type IntoIter = slice::Iter<'a, Tag>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tags.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut List {
+    type Item = &'a mut Tag;
+    type IntoIter = slice::IterMut<'a, Tag>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.tags.iter_mut()
+    }
+}
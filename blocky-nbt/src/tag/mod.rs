@@ -1,23 +1,30 @@
 mod kind;
 mod index;
 mod parser;
+mod writer;
+mod patch;
+mod convert;
+mod list;
 
 pub use kind::*;
 pub use index::*;
+pub use list::List;
 pub use parser::*;
+pub use writer::{SnbtOptions, QuoteStyle};
+pub use patch::{NbtPatch, Change, PathSegment};
+
+pub(crate) use patch::path_to_string;
+pub(crate) use convert::Number;
 
 #[cfg(feature = "preserve-order")]
 pub use indexmap::IndexMap as Map;
 #[cfg(not(feature = "preserve-order"))]
 pub use std::collections::HashMap as Map;
 
-use regex::Regex;
+use crate::NbtError;
+use writer::Writer;
 use std::fmt;
 
-lazy_static! {
-    static ref SIMPLE_PATTERN: Regex = Regex::new("^[A-Za-z0-9._+-]+$").unwrap();
-}
-
 #[derive(Debug, PartialEq, Clone)]
 pub enum Tag {
     End,
@@ -29,62 +36,12 @@ pub enum Tag {
     Double(f64),
     ByteArray(Vec<i8>),
     String(String),
-    List(Vec<Self>),
+    List(List),
     Compound(Map<String, Self>),
     IntArray(Vec<i32>),
     LongArray(Vec<i64>),
 }
 
-macro_rules! into_tag {
-    ($input:ty, $output:ident) => {
-        impl Into<Tag> for $input {
-            fn into(self) -> Tag {
-                Tag::$output(self)
-            }
-        }
-
-        impl Into<$input> for Tag {
-            fn into(self) -> $input {
-                match self {
-                    Tag::$output(value) => value,
-                    _ => panic!("cannot convert tag"),
-                }
-            }
-        }
-
-        impl<'b> Into<$input> for &'b Tag {
-            fn into(self) -> $input {
-                match self {
-                    Tag::$output(value) => value.to_owned(),
-                    _ => panic!("cannot convert tag"),
-                }
-            }
-        }
-    };
-}
-
-macro_rules! into_tags {
-    { $({ $input:ty, $output:ident }),* $(,)* } => {
-        $(into_tag!($input, $output);)*
-    };
-}
-
-into_tags! {
-    { i8, Byte },
-    { i16, Short },
-    { i32, Int },
-    { i64, Long },
-
-    { f32, Float },
-    { f64, Double },
-
-    { String, String },
-
-    { Vec<i8>, ByteArray },
-    { Vec<i32>, IntArray },
-    { Vec<i64>, LongArray },
-}
-
 impl Tag {
     pub fn kind(&self) -> Kind {
         match self {
@@ -97,11 +54,7 @@ impl Tag {
             Self::Double(_) => Kind::Double,
             Self::ByteArray(_) => Kind::ByteArray,
             Self::String(_) => Kind::String,
-            Self::List(v) => Kind::List(
-                v.get(0)
-                    .map(|tag| tag.kind().id())
-                    .unwrap_or(Kind::End.id()),
-            ),
+            Self::List(v) => Kind::List(v.id()),
             Self::Compound(_) => Kind::Compound,
             Self::IntArray(_) => Kind::IntArray,
             Self::LongArray(_) => Kind::LongArray,
@@ -116,108 +69,52 @@ impl Tag {
         index.index_into_mut(self)
     }
 
+    // panics where try_insert would fail
     pub fn insert<I: Index>(&mut self, index: I, value: Self) {
-        *index.index_or_insert(self) = value;
+        if let Err(e) = self.try_insert(index, value) {
+            panic!("{}", e);
+        }
     }
-}
 
-fn quote_and_escape(s: &str) -> String {
-    let mut builder = String::new();
-    let mut quote_chr = None;
-
-    for chr in s.chars() {
-        if chr == '\\' {
-            builder.push('\\');
-        } else if chr == '"' || chr == '\'' {
-            if quote_chr.is_none() {
-                quote_chr = Some(if chr == '"' { '\'' } else { '"' });
-            }
-
-            if quote_chr.is_some() && quote_chr.unwrap() == chr {
-                builder.push('\\');
-            }
+    // sets a compound entry or replaces a list element, failing for other tags or a list
+    // index out of range
+    pub fn try_insert<I: Index>(&mut self, index: I, value: Self) -> Result<(), NbtError> {
+        let kind = self.kind();
+
+        match index.index_or_insert(self) {
+            Some(tag) => {
+                *tag = value;
+                Ok(())
+            },
+            None => Err(NbtError::InvalidIndex(kind)),
         }
+    }
+}
 
-        builder.push(chr);
+impl Tag {
+    pub fn to_snbt(&self) -> Result<String, NbtError> {
+        self.to_snbt_with(&SnbtOptions::default())
     }
 
-    let quote_chr = quote_chr.unwrap_or('"');
+    pub fn to_snbt_pretty(&self, indent: usize) -> Result<String, NbtError> {
+        self.to_snbt_with(&SnbtOptions::pretty(indent))
+    }
 
-    builder.insert(0, quote_chr);
-    builder.push(quote_chr);
-    builder
+    // end tags inside compounds are skipped, anywhere else they are an error
+    pub fn to_snbt_with(&self, options: &SnbtOptions) -> Result<String, NbtError> {
+        Writer::new(options).write(self)
+    }
 }
 
+// end tags, which to_snbt rejects, are shown as END so formatting never fails
 impl fmt::Display for Tag {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self {
-            Self::End => panic!("cannot convert end to string"),
-
-            Self::Byte(value) => format!("{}b", *value),
-            Self::Short(value) => format!("{}s", *value),
-            Self::Int(value) => format!("{}", *value),
-            Self::Long(value) => format!("{}L", *value),
-
-            Self::Float(value) => format!("{}f", *value),
-            Self::Double(value) => format!("{}d", *value),
-
-            Self::String(s) => quote_and_escape(&s),
-
-            Self::List(v) => {
-                let mut items = vec![];
-
-                for tag in v {
-                    items.push(tag.to_string());
-                }
-
-                format!("[{}]", items.join(","))
-            },
-
-            Self::Compound(m) => {
-                let mut items = vec![];
-
-                for (name, tag) in m {
-                    let value = tag.to_string();
-                    let key = if SIMPLE_PATTERN.is_match(&name) {
-                        name.clone()
-                    } else {
-                        quote_and_escape(&name)
-                    };
+        let snbt = Writer::new(&SnbtOptions::default())
+            .with_end_marker()
+            .write(self)
+            .expect("only end tags fail to write");
 
-                    items.push(format!("{}:{}", key, value));
-                }
-
-                format!("{{{}}}", items.join(","))
-            },
-
-            Self::ByteArray(v) => {
-                let mut items = vec![];
-
-                for value in v {
-                    items.push(Tag::Byte(*value).to_string());
-                }
-
-                format!("[B;{}]", items.join(","))
-            },
-            Self::IntArray(v) => {
-                let mut items = vec![];
-
-                for value in v {
-                    items.push(Tag::Int(*value).to_string());
-                }
-
-                format!("[I;{}]", items.join(","))
-            },
-            Self::LongArray(v) => {
-                let mut items = vec![];
-
-                for value in v {
-                    items.push(Tag::Long(*value).to_string());
-                }
-
-                format!("[L;{}]", items.join(","))
-            },
-        })
+        f.write_str(&snbt)
     }
 }
 
@@ -1,18 +1,135 @@
 mod reader;
+mod path;
 
 use reader::*;
 
-use crate::{Tag, Nbt, Kind, Map};
+pub use path::NbtPath;
+
+use crate::{Tag, Nbt, Kind, Map, NbtError};
 use regex::Regex;
 
+// digits may be separated by any number of underscores, but can't start or end with one
+const DIGITS: &str = "[0-9](?:_*[0-9])*";
+
 lazy_static! {
-    static ref DOUBLE_PATTERN_NOSUFFIX: Regex = Regex::new("^(?i)[-+]?(?:[0-9]+[.]|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?$").unwrap();
-    static ref DOUBLE_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?d$").unwrap();
-    static ref FLOAT_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:[0-9]+[.]?|[0-9]*[.][0-9]+)(?:e[-+]?[0-9]+)?f$").unwrap();
-    static ref BYTE_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:0|[1-9][0-9]*)b$").unwrap();
-    static ref LONG_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:0|[1-9][0-9]*)l$").unwrap();
-    static ref SHORT_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:0|[1-9][0-9]*)s$").unwrap();
-    static ref INT_PATTERN: Regex = Regex::new("^(?i)[-+]?(?:0|[1-9][0-9]*)$").unwrap();
+    static ref INTEGER_PATTERN: Regex = Regex::new(
+        "^(?i)([-+])?(?:0x([0-9a-f](?:_*[0-9a-f])*)|0b([01](?:_*[01])*)|([0-9](?:_*[0-9])*))(?:([su])?([bsil]))?$"
+    ).unwrap();
+    static ref DOUBLE_PATTERN_NOSUFFIX: Regex = Regex::new(&format!(
+        "^(?i)[-+]?(?:(?:{0}[.]|(?:{0})?[.]{0})(?:e[-+]?{0})?|{0}e[-+]?{0})$", DIGITS
+    )).unwrap();
+    static ref DOUBLE_PATTERN: Regex = Regex::new(&format!(
+        "^(?i)[-+]?(?:{0}[.]?|(?:{0})?[.]{0})(?:e[-+]?{0})?d$", DIGITS
+    )).unwrap();
+    static ref FLOAT_PATTERN: Regex = Regex::new(&format!(
+        "^(?i)[-+]?(?:{0}[.]?|(?:{0})?[.]{0})(?:e[-+]?{0})?f$", DIGITS
+    )).unwrap();
+    // not accepted by vanilla, but written by us so nan and infinity survive a round trip
+    static ref SPECIAL_PATTERN: Regex = Regex::new("^(?:NaN|[-+]?Infinity)[fFdD]$").unwrap();
+}
+
+// the integer in a literal, range checked against the width of its type
+fn parse_integer(captures: &regex::Captures) -> Result<Tag, String> {
+    let negative = captures.get(1).is_some_and(|sign| sign.as_str() == "-");
+
+    let (digits, radix) = if let Some(digits) = captures.get(2) {
+        (digits, 16)
+    } else if let Some(digits) = captures.get(3) {
+        (digits, 2)
+    } else {
+        (captures.get(4).unwrap(), 10)
+    };
+
+    let digits = digits.as_str().replace('_', "");
+    let value = u64::from_str_radix(&digits, radix)
+        .map_err(|_| String::from("number is too large"))? as i128;
+
+    // decimal is signed unless marked unsigned, hex and binary are bit patterns unless negative
+    let unsigned = match captures.get(5).map(|m| m.as_str().to_ascii_lowercase()) {
+        Some(signedness) => signedness == "u",
+        None => radix != 10 && !negative,
+    };
+
+    if unsigned && negative {
+        return Err(String::from("unsigned number cannot be negative"));
+    }
+
+    let suffix = captures.get(6).map_or(String::from("i"), |m| m.as_str().to_ascii_lowercase());
+    let bits = match suffix.as_str() {
+        "b" => 8,
+        "s" => 16,
+        "l" => 64,
+        _ => 32,
+    };
+
+    let (min, max) = if unsigned {
+        (0, (1i128 << bits) - 1)
+    } else {
+        (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+    };
+
+    let value = if negative { -value } else { value };
+
+    if value < min || value > max {
+        return Err(format!("number is out of range for a {} bit integer", bits));
+    }
+
+    Ok(match bits {
+        8 => Tag::Byte(value as i8),
+        16 => Tag::Short(value as i16),
+        64 => Tag::Long(value as i64),
+        _ => Tag::Int(value as i32),
+    })
+}
+
+fn parse_float<T: std::str::FromStr>(s: &str) -> Option<T> {
+    s.replace('_', "").parse().ok()
+}
+
+fn parse_uuid(s: &str) -> Option<Tag> {
+    let groups: Vec<_> = s.split('-').collect();
+    let lens = [8, 4, 4, 4, 12];
+
+    if groups.len() != lens.len() {
+        return None;
+    }
+
+    let mut value = 0u128;
+
+    for (group, len) in groups.iter().zip(lens.iter()) {
+        if group.len() != *len || !group.chars().all(|chr| chr.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        value = value << (len * 4) | u128::from_str_radix(group, 16).ok()?;
+    }
+
+    // stored most significant int first, as vanilla does
+    Some(Tag::IntArray((0..4).rev().map(|i| (value >> (i * 32)) as u32 as i32).collect()))
+}
+
+// mixed lists are stored as compounds with an empty key, like vanilla's list wrapping
+fn wrap_list(v: Vec<Tag>) -> Vec<Tag> {
+    let id = match v.first() {
+        Some(tag) => tag.kind().id(),
+        None => return v,
+    };
+
+    if v.iter().all(|tag| tag.kind().id() == id) {
+        return v;
+    }
+
+    v.into_iter()
+        .map(|tag| match tag {
+            Tag::Compound(m) if !(m.len() == 1 && m.contains_key("")) => Tag::Compound(m),
+            tag => {
+                let mut m = Map::new();
+
+                m.insert(String::new(), tag);
+                Tag::Compound(m)
+            },
+        })
+        .collect()
 }
 
 macro_rules! read_array {
@@ -24,19 +141,20 @@ macro_rules! read_array {
                 let start = $self.reader.position();
                 let tag = $self.read_value()?;
 
-                if tag.kind() != Kind::$inner {
-                    $self.reader.set_position(start);
-                    anyhow::bail!("array has mixed tags");
+                match tag {
+                    Tag::$inner(value) => v.push(value),
+                    tag => {
+                        $self.reader.set_position(start);
+                        return Err($self.reader.error(format!("array of {:?} cannot hold {:?}", Kind::$inner, tag.kind())));
+                    },
                 }
 
-                v.push(tag.into());
-
                 if !$self.has_separator()? {
                     break;
                 }
 
                 if $self.reader.done() {
-                    anyhow::bail!("expected array closure")
+                    return Err($self.reader.error_expected("unexpected end of input", &["value", "]"]));
                 }
             }
 
@@ -54,73 +172,139 @@ impl Parser {
         Self { reader: Reader::new(s.into()) }
     }
 
-    fn expect(&mut self, chr: char) -> anyhow::Result<()> {
+    fn expect(&mut self, chr: char) -> Result<(), NbtError> {
         self.reader.skip_whitespace()?;
         self.reader.expect(chr)?;
 
         Ok(())
     }
 
-    fn read_key(&mut self) -> anyhow::Result<String> {
+    // a closing bracket where a separator would also have been fine
+    fn expect_closure(&mut self, chr: char) -> Result<(), NbtError> {
+        self.reader.skip_whitespace()?;
+
+        let closure = chr.to_string();
+
+        match self.reader.peek() {
+            Ok(read_chr) if read_chr == chr => {
+                self.reader.skip();
+                Ok(())
+            },
+            Ok(read_chr) => Err(self.reader.error_expected(format!("unexpected {}", read_chr), &[",", &closure])),
+            Err(_) => Err(self.reader.error_expected("unexpected end of input", &[",", &closure])),
+        }
+    }
+
+    fn read_key(&mut self) -> Result<String, NbtError> {
         self.reader.skip_whitespace()?;
 
         if self.reader.done() {
-            anyhow::bail!("missing expected key");
+            return Err(self.reader.error_expected("unexpected end of input", &["key"]));
         }
 
         self.reader.read_string()
     }
 
-    fn read_typed_value(&mut self) -> anyhow::Result<Tag> {
+    fn read_typed_value(&mut self) -> Result<Tag, NbtError> {
         self.reader.skip_whitespace()?;
 
         let start = self.reader.position();
 
         if Reader::is_quote(self.reader.peek()?) {
-            Ok(self.reader.read_quoted_string()?.into())
-        } else {
-            let s = self.reader.read_unquoted_string()?;
+            return Ok(self.reader.read_quoted_string()?.into());
+        }
+
+        let s = self.reader.read_unquoted_string()?;
 
-            if s.is_empty() {
+        if s.is_empty() {
+            self.reader.set_position(start);
+            return Err(self.reader.error_expected(format!("unexpected {}", self.reader.peek()?), &["value"]));
+        }
+
+        if !self.reader.done() && self.reader.peek()? == '(' {
+            return self.read_operation(&s, start);
+        }
+
+        match Self::parse_type(&s) {
+            Ok(tag) => Ok(tag.unwrap_or(Tag::String(s))),
+            Err(message) => {
                 self.reader.set_position(start);
-                Err(anyhow::anyhow!("missing expected value"))
-            } else {
-                Self::parse_type(&s).or_else(|_| Ok(Tag::String(s)))
-            }
+                Err(self.reader.error(message))
+            },
         }
     }
 
-    fn parse_type(s: &str) -> anyhow::Result<Tag> {
+    // vanilla's bool(...) and uuid(...) value operations
+    fn read_operation(&mut self, name: &str, start: usize) -> Result<Tag, NbtError> {
+        self.reader.skip();
+
+        let arg_start = self.reader.position();
+        let tag = match name {
+            "bool" => match self.read_value()? {
+                Tag::Byte(value) => Tag::Byte((value != 0) as i8),
+                Tag::Short(value) => Tag::Byte((value != 0) as i8),
+                Tag::Int(value) => Tag::Byte((value != 0) as i8),
+                Tag::Long(value) => Tag::Byte((value != 0) as i8),
+                Tag::Float(value) => Tag::Byte((value != 0.0) as i8),
+                Tag::Double(value) => Tag::Byte((value != 0.0) as i8),
+                _ => {
+                    self.reader.set_position(arg_start);
+                    return Err(self.reader.error_expected("invalid argument", &["number", "true", "false"]));
+                },
+            },
+            "uuid" => {
+                self.reader.skip_whitespace()?;
+
+                let arg_start = self.reader.position();
+
+                match parse_uuid(&self.reader.read_string()?) {
+                    Some(tag) => tag,
+                    None => {
+                        self.reader.set_position(arg_start);
+                        return Err(self.reader.error_expected("invalid argument", &["uuid"]));
+                    },
+                }
+            },
+            _ => {
+                self.reader.set_position(start);
+                return Err(self.reader.error_expected(format!("unknown operation {}", name), &["bool", "uuid"]));
+            },
+        };
+
+        self.expect(')')?;
+        Ok(tag)
+    }
+
+    fn parse_type(s: &str) -> Result<Option<Tag>, String> {
         let len = s.len();
 
-        Ok(if FLOAT_PATTERN.is_match(&s) {
-            Tag::Float(s[..len - 1].parse()?)
-        } else if BYTE_PATTERN.is_match(&s) {
-            Tag::Byte(s[..len - 1].parse()?)
-        } else if LONG_PATTERN.is_match(&s) {
-            Tag::Long(s[..len - 1].parse()?)
-        } else if SHORT_PATTERN.is_match(&s) {
-            Tag::Short(s[..len - 1].parse()?)
-        } else if INT_PATTERN.is_match(&s) {
-            Tag::Int(s.parse()?)
-        } else if DOUBLE_PATTERN.is_match(&s) {
-            Tag::Double(s[..len - 1].parse()?)
-        } else if DOUBLE_PATTERN_NOSUFFIX.is_match(&s) {
-            Tag::Double(s.parse()?)
-        } else if s.to_lowercase() == "true" {
+        Ok(Some(if SPECIAL_PATTERN.is_match(s) {
+            match &s[len - 1..] {
+                "f" | "F" => Tag::Float(s[..len - 1].parse().unwrap()),
+                _ => Tag::Double(s[..len - 1].parse().unwrap()),
+            }
+        } else if let Some(captures) = INTEGER_PATTERN.captures(s) {
+            parse_integer(&captures)?
+        } else if FLOAT_PATTERN.is_match(s) {
+            Tag::Float(parse_float(&s[..len - 1]).unwrap())
+        } else if DOUBLE_PATTERN.is_match(s) {
+            Tag::Double(parse_float(&s[..len - 1]).unwrap())
+        } else if DOUBLE_PATTERN_NOSUFFIX.is_match(s) {
+            Tag::Double(parse_float(s).unwrap())
+        } else if s.eq_ignore_ascii_case("true") {
             Tag::Byte(1)
-        } else if s.to_lowercase() == "false" {
+        } else if s.eq_ignore_ascii_case("false") {
             Tag::Byte(0)
         } else {
-            anyhow::bail!("unknown type")
-        })
+            return Ok(None);
+        }))
     }
 
-    pub fn read_value(&mut self) -> anyhow::Result<Tag> {
+    pub fn read_value(&mut self) -> Result<Tag, NbtError> {
         self.reader.skip_whitespace()?;
 
         if self.reader.done() {
-            anyhow::bail!("missing expected value");
+            return Err(self.reader.error_expected("unexpected end of input", &["value"]));
         }
 
         let chr = self.reader.peek()?;
@@ -134,7 +318,18 @@ impl Parser {
         }
     }
 
-    fn has_separator(&mut self) -> anyhow::Result<bool> {
+    // fails unless only whitespace is left
+    pub fn finish(&mut self) -> Result<(), NbtError> {
+        self.reader.skip_whitespace()?;
+
+        if self.reader.done() {
+            Ok(())
+        } else {
+            Err(self.reader.error_expected(format!("unexpected {}", self.reader.peek()?), &["end of input"]))
+        }
+    }
+
+    fn has_separator(&mut self) -> Result<bool, NbtError> {
         self.reader.skip_whitespace()?;
 
         Ok(if !self.reader.done() && self.reader.peek()? == ',' {
@@ -147,7 +342,7 @@ impl Parser {
         })
     }
 
-    pub fn read_struct(&mut self) -> anyhow::Result<Tag> {
+    pub fn read_struct(&mut self) -> Result<Tag, NbtError> {
         self.expect('{')?;
         self.reader.skip_whitespace()?;
 
@@ -157,9 +352,9 @@ impl Parser {
             let start = self.reader.position();
             let key = self.read_key()?;
 
-            if key.is_empty() {
-                self.reader.set_position(start);
-                anyhow::bail!("missing expected key");
+            // only a quoted key may be empty
+            if self.reader.position() == start {
+                return Err(self.reader.error_expected(format!("unexpected {}", self.reader.peek()?), &["key", "}"]));
             }
 
             self.expect(':')?;
@@ -172,15 +367,15 @@ impl Parser {
             }
 
             if self.reader.done() {
-                anyhow::bail!("expected struct closure");
+                return Err(self.reader.error_expected("unexpected end of input", &["key", "}"]));
             }
         }
 
-        self.expect('}')?;
+        self.expect_closure('}')?;
         Ok(Tag::Compound(m))
     }
 
-    pub fn read_array_tag(&mut self) -> anyhow::Result<Tag> {
+    pub fn read_array_tag(&mut self) -> Result<Tag, NbtError> {
         self.expect('[')?;
 
         // store start position in case error
@@ -192,7 +387,7 @@ impl Parser {
 
         if self.reader.done() {
             self.reader.set_position(start);
-            anyhow::bail!("missing expected array value");
+            return Err(self.reader.error_expected("unexpected end of input", &["value", "]"]));
         }
 
         let tag = if kind == 'B' {
@@ -202,52 +397,42 @@ impl Parser {
         } else if kind == 'I' {
             read_array!(self, Int, i32)
         } else {
-            anyhow::bail!("unknown array type");
+            self.reader.set_position(start);
+            return Err(self.reader.error_expected(format!("unknown array type {}", kind), &["B", "I", "L"]));
         };
 
-        self.expect(']')?;
+        self.expect_closure(']')?;
         Ok(tag)
     }
 
 
-    pub fn read_list_tag(&mut self) -> anyhow::Result<Tag> {
+    pub fn read_list_tag(&mut self) -> Result<Tag, NbtError> {
         self.expect('[')?;
         self.reader.skip_whitespace()?;
 
         if self.reader.done() {
-            anyhow::bail!("missing expected array value");
+            return Err(self.reader.error_expected("unexpected end of input", &["value", "]"]));
         }
 
-        let mut kind = Kind::End;
         let mut v = vec![];
 
         while self.reader.peek()? != ']' {
-            let start = self.reader.position();
-            let tag = self.read_value()?;
-
-            if kind == Kind::End {
-                kind = tag.kind();
-            } else if kind != tag.kind() {
-                self.reader.set_position(start);
-                anyhow::bail!("array has mixed types");
-            }
-
-            v.push(tag);
+            v.push(self.read_value()?);
 
             if !self.has_separator()? {
                 break;
             }
 
             if self.reader.done() {
-                anyhow::bail!("expected array closure")
+                return Err(self.reader.error_expected("unexpected end of input", &["value", "]"]));
             }
         }
 
-        self.expect(']')?;
-        Ok(Tag::List(v))
+        self.expect_closure(']')?;
+        Ok(Tag::List(wrap_list(v).into()))
     }
 
-    pub fn read_list(&mut self) -> anyhow::Result<Tag> {
+    pub fn read_list(&mut self) -> Result<Tag, NbtError> {
         if self.reader.has_remaining(3) && !Reader::is_quote(self.reader.peek_nth(1)?) && self.reader.peek_nth(2)? == ';' {
             self.read_array_tag()
         } else {
@@ -257,10 +442,20 @@ impl Parser {
 }
 
 impl Nbt {
-    pub fn parse<S: Into<String>>(s: S) -> anyhow::Result<Tag> {
+    // anything after the value is ignored, see parse_strict
+    pub fn parse<S: Into<String>>(s: S) -> Result<Tag, NbtError> {
+        let mut parser = Parser::new(s);
+        let tag = parser.read_value()?;
+
+        Ok(tag)
+    }
+
+    pub fn parse_strict<S: Into<String>>(s: S) -> Result<Tag, NbtError> {
         let mut parser = Parser::new(s);
         let tag = parser.read_value()?;
 
+        parser.finish()?;
+
         Ok(tag)
     }
 }
@@ -0,0 +1,446 @@
+use super::{Parser, Reader};
+use crate::{Tag, List, Map, Index, NbtError};
+use crate::tag::patch::merge_compound;
+
+// one step of a path, following vanilla's NbtPathArgument nodes
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    // {filter} at the start of a path
+    MatchRoot(Tag),
+    // name
+    Child(String),
+    // name{filter}
+    MatchChild(String, Tag),
+    // []
+    All,
+    // [index], counting from the end when negative
+    Index(i32),
+    // [{filter}]
+    MatchElement(Tag),
+}
+
+// vanilla's partial comparison, where a filter matches any tag holding at least its contents
+fn matches(filter: &Tag, tag: &Tag) -> bool {
+    match (filter, tag) {
+        (Tag::Compound(filter), Tag::Compound(m)) => filter.iter()
+            .all(|(name, filter)| m.get(name).is_some_and(|tag| matches(filter, tag))),
+        (Tag::List(filter), Tag::List(v)) if !filter.is_empty() => filter.iter()
+            .all(|filter| v.iter().any(|tag| matches(filter, tag))),
+        (Tag::List(_), Tag::List(v)) => v.is_empty(),
+        _ => filter == tag,
+    }
+}
+
+// only tags of the kind a list holds can replace its elements
+fn fits(v: &List, tag: &Tag) -> bool {
+    v.id() == tag.kind().id()
+}
+
+fn resolve(tag: &Tag, index: i32) -> Option<usize> {
+    let len = match tag {
+        Tag::List(v) => v.len() as i64,
+        _ => return None,
+    };
+
+    let index = if index < 0 { len + index as i64 } else { index as i64 };
+
+    if index >= 0 && index < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+impl Node {
+    // the tag created for a missing parent of this node
+    fn empty_parent(&self) -> Tag {
+        match self {
+            Self::All | Self::Index(_) | Self::MatchElement(_) => Tag::List(List::new()),
+            _ => Tag::Compound(Map::new()),
+        }
+    }
+
+    fn get<'a>(&self, tag: &'a Tag, out: &mut Vec<&'a Tag>) {
+        match self {
+            Self::MatchRoot(filter) => {
+                if matches(filter, tag) {
+                    out.push(tag);
+                }
+            },
+            Self::Child(name) => out.extend(name.index_into(tag)),
+            Self::MatchChild(name, filter) => {
+                out.extend(name.index_into(tag).filter(|tag| matches(filter, tag)));
+            },
+            Self::All => {
+                if let Tag::List(v) = tag {
+                    out.extend(v.iter());
+                }
+            },
+            Self::Index(index) => {
+                if let Some(index) = resolve(tag, *index) {
+                    out.extend(index.index_into(tag));
+                }
+            },
+            Self::MatchElement(filter) => {
+                if let Tag::List(v) = tag {
+                    out.extend(v.iter().filter(|tag| matches(filter, tag)));
+                }
+            },
+        }
+    }
+
+    fn get_mut<'a>(&self, tag: &'a mut Tag, out: &mut Vec<&'a mut Tag>) {
+        match self {
+            Self::MatchRoot(filter) => {
+                if matches(filter, tag) {
+                    out.push(tag);
+                }
+            },
+            Self::Child(name) => out.extend(name.index_into_mut(tag)),
+            Self::MatchChild(name, filter) => {
+                out.extend(name.index_into_mut(tag).filter(|tag| matches(filter, tag)));
+            },
+            Self::All => {
+                if let Tag::List(v) = tag {
+                    out.extend(v.iter_mut());
+                }
+            },
+            Self::Index(index) => {
+                if let Some(index) = resolve(tag, *index) {
+                    out.extend(index.index_into_mut(tag));
+                }
+            },
+            Self::MatchElement(filter) => {
+                if let Tag::List(v) = tag {
+                    out.extend(v.iter_mut().filter(|tag| matches(filter, tag)));
+                }
+            },
+        }
+    }
+
+    // like get_mut, but adds what is missing so the path can be written to
+    fn get_or_create<'a>(&self, tag: &'a mut Tag, empty: Tag, out: &mut Vec<&'a mut Tag>) {
+        match (self, &mut *tag) {
+            (Self::Child(name), Tag::Compound(m)) => {
+                m.entry(name.clone()).or_insert(empty);
+            },
+            (Self::MatchChild(name, filter), Tag::Compound(m)) => {
+                m.entry(name.clone()).or_insert_with(|| filter.clone());
+            },
+            (Self::All, Tag::List(v)) if v.is_empty() => {
+                let _ = v.push(empty);
+            },
+            (Self::MatchElement(filter), Tag::List(v)) if !v.iter().any(|tag| matches(filter, tag)) => {
+                // a filter of the wrong kind for the list adds nothing
+                let _ = v.push(filter.clone());
+            },
+            _ => {},
+        }
+
+        self.get_mut(tag, out);
+    }
+
+    fn set(&self, tag: &mut Tag, value: &Tag) -> usize {
+        let mut count = 0;
+
+        match (self, tag) {
+            (Self::Child(name), Tag::Compound(m)) if m.get(name) != Some(value) => {
+                m.insert(name.clone(), value.clone());
+                count += 1;
+            },
+            (Self::MatchChild(name, filter), Tag::Compound(m)) => {
+                if let Some(tag) = m.get_mut(name) {
+                    if matches(filter, tag) && tag != value {
+                        *tag = value.clone();
+                        count += 1;
+                    }
+                }
+            },
+            // fails for an empty list declared with another kind
+            (Self::All, Tag::List(v)) if v.is_empty() => count += v.push(value.clone()).is_ok() as usize,
+            (Self::All, Tag::List(v)) if fits(v, value) => {
+                for tag in v.iter_mut().filter(|tag| *tag != value) {
+                    *tag = value.clone();
+                    count += 1;
+                }
+            },
+            (Self::Index(index), tag) => {
+                if let (Some(index), Tag::List(v)) = (resolve(tag, *index), tag) {
+                    if fits(v, value) && v[index] != *value {
+                        v[index] = value.clone();
+                        count += 1;
+                    }
+                }
+            },
+            (Self::MatchElement(filter), Tag::List(v)) if fits(v, value) => {
+                for tag in v.iter_mut().filter(|tag| matches(filter, tag) && *tag != value) {
+                    *tag = value.clone();
+                    count += 1;
+                }
+            },
+            _ => {},
+        }
+
+        count
+    }
+
+    fn remove(&self, tag: &mut Tag) -> usize {
+        let len = match &*tag {
+            Tag::Compound(m) => m.len(),
+            Tag::List(v) => v.len(),
+            _ => return 0,
+        };
+
+        match (self, &mut *tag) {
+            (Self::Child(name), Tag::Compound(m)) => m.retain(|key, _| key != name),
+            (Self::MatchChild(name, filter), Tag::Compound(m)) => {
+                m.retain(|key, tag| key != name || !matches(filter, tag));
+            },
+            (Self::All, Tag::List(v)) => v.clear(),
+            (Self::Index(index), tag) => {
+                if let (Some(index), Tag::List(v)) = (resolve(tag, *index), tag) {
+                    v.remove(index);
+                }
+            },
+            (Self::MatchElement(filter), Tag::List(v)) => v.retain(|tag| !matches(filter, tag)),
+            _ => {},
+        }
+
+        len - match tag {
+            Tag::Compound(m) => m.len(),
+            Tag::List(v) => v.len(),
+            _ => len,
+        }
+    }
+}
+
+// a path in the syntax of vanilla's data commands, like Inventory[{Slot:3b}].tag.display.Name
+//
+// array elements aren't tags of their own here, so paths can't step into byte, int or long arrays
+#[derive(Debug, Clone, PartialEq)]
+pub struct NbtPath {
+    nodes: Vec<Node>,
+}
+
+impl NbtPath {
+    pub fn parse<S: Into<String>>(s: S) -> Result<Self, NbtError> {
+        let mut parser = Parser::new(s);
+        let nodes = parser.read_path()?;
+
+        Ok(Self { nodes })
+    }
+
+    pub fn get_all<'a>(&self, tag: &'a Tag) -> Vec<&'a Tag> {
+        let mut tags = vec![tag];
+
+        for node in &self.nodes {
+            let mut out = vec![];
+
+            for tag in tags {
+                node.get(tag, &mut out);
+            }
+
+            tags = out;
+        }
+
+        tags
+    }
+
+    pub fn get_all_mut<'a>(&self, tag: &'a mut Tag) -> Vec<&'a mut Tag> {
+        let mut tags = vec![tag];
+
+        for node in &self.nodes {
+            let mut out = vec![];
+
+            for tag in tags {
+                node.get_mut(tag, &mut out);
+            }
+
+            tags = out;
+        }
+
+        tags
+    }
+
+    // the tags holding the last node, created where missing
+    fn parents_or_create<'a>(&self, tag: &'a mut Tag) -> Vec<&'a mut Tag> {
+        let mut tags = vec![tag];
+
+        for (node, next) in self.nodes.iter().zip(&self.nodes[1..]) {
+            let mut out = vec![];
+
+            for tag in tags {
+                node.get_or_create(tag, next.empty_parent(), &mut out);
+            }
+
+            tags = out;
+        }
+
+        tags
+    }
+
+    fn last(&self) -> &Node {
+        self.nodes.last().unwrap()
+    }
+
+    // replaces every matching tag, returning how many were changed
+    pub fn set(&self, tag: &mut Tag, value: &Tag) -> usize {
+        let last = self.last();
+
+        self.parents_or_create(tag)
+            .into_iter()
+            .map(|parent| last.set(parent, value))
+            .sum()
+    }
+
+    pub fn remove(&self, tag: &mut Tag) -> usize {
+        let mut parents = vec![tag];
+
+        for node in &self.nodes[..self.nodes.len() - 1] {
+            let mut out = vec![];
+
+            for tag in parents {
+                node.get_mut(tag, &mut out);
+            }
+
+            parents = out;
+        }
+
+        let last = self.last();
+
+        parents.into_iter()
+            .map(|parent| last.remove(parent))
+            .sum()
+    }
+
+    // inserts into every matching list at an index, where negative indices count back from
+    // the end and -1 appends
+    pub fn insert(&self, tag: &mut Tag, index: i32, value: &Tag) -> usize {
+        let mut count = 0;
+
+        for target in self.targets_or_create(tag, Tag::List(List::new())) {
+            if let Tag::List(v) = target {
+                let len = v.len() as i64;
+                let index = if index < 0 { len + 1 + index as i64 } else { index as i64 };
+
+                if index >= 0 && index <= len && v.insert(index as usize, value.clone()).is_ok() {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    // deep merges into every matching compound, creating it if missing
+    pub fn merge(&self, tag: &mut Tag, value: &Map<String, Tag>) -> usize {
+        let mut count = 0;
+
+        for target in self.targets_or_create(tag, Tag::Compound(Map::new())) {
+            if let Tag::Compound(m) = target {
+                let old = m.clone();
+
+                merge_compound(m, value);
+
+                if *m != old {
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    fn targets_or_create<'a>(&self, tag: &'a mut Tag, empty: Tag) -> Vec<&'a mut Tag> {
+        let last = self.last();
+        let mut out = vec![];
+
+        for parent in self.parents_or_create(tag) {
+            last.get_or_create(parent, empty.clone(), &mut out);
+        }
+
+        out
+    }
+}
+
+impl Parser {
+    fn read_path(&mut self) -> Result<Vec<Node>, NbtError> {
+        let mut nodes = vec![];
+
+        if self.reader.done() {
+            return Err(self.reader.error_expected("unexpected end of input", &["path"]));
+        }
+
+        while !self.reader.done() {
+            nodes.push(self.read_path_node(nodes.is_empty())?);
+
+            if !self.reader.done() {
+                let chr = self.reader.peek()?;
+
+                if chr != '[' && chr != '{' {
+                    self.reader.expect('.')?;
+
+                    if self.reader.done() {
+                        return Err(self.reader.error_expected("unexpected end of input", &["key"]));
+                    }
+                }
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    fn read_path_node(&mut self, first: bool) -> Result<Node, NbtError> {
+        let chr = self.reader.peek()?;
+
+        if chr == '{' {
+            if !first {
+                return Err(self.reader.error("filters can only follow a key or start a path"));
+            }
+
+            return Ok(Node::MatchRoot(self.read_struct()?));
+        }
+
+        if chr == '[' {
+            self.reader.skip();
+
+            let chr = self.reader.peek()?;
+            let node = if chr == '{' {
+                Node::MatchElement(self.read_struct()?)
+            } else if chr == ']' {
+                Node::All
+            } else {
+                let start = self.reader.position();
+                let index = self.reader.read_while(|chr| chr == '-' || chr.is_ascii_digit())?;
+
+                match index.parse() {
+                    Ok(index) => Node::Index(index),
+                    Err(_) => {
+                        self.reader.set_position(start);
+                        return Err(self.reader.error_expected("invalid index", &["index", "{", "]"]));
+                    },
+                }
+            };
+
+            self.reader.expect(']')?;
+            return Ok(node);
+        }
+
+        let start = self.reader.position();
+        let name = if Reader::is_quote(chr) {
+            self.reader.read_quoted_string()?
+        } else {
+            self.reader.read_while(|chr| !chr.is_whitespace() && !"\"'[]{}.".contains(chr))?
+        };
+
+        if self.reader.position() == start {
+            return Err(self.reader.error_expected(format!("unexpected {}", chr), &["key", "[", "{"]));
+        }
+
+        if !self.reader.done() && self.reader.peek()? == '{' {
+            Ok(Node::MatchChild(name, self.read_struct()?))
+        } else {
+            Ok(Node::Child(name))
+        }
+    }
+}
@@ -1,21 +1,77 @@
+use crate::{NbtError, ParseError};
+
+// chars shown either side of the error column when a line is too long to show whole
+const CONTEXT: usize = 40;
+
+// positions are char indices, never byte offsets into the original text
 pub struct Reader {
-    text: String,
     chars: Vec<char>,
     position: usize,
 }
 
 impl Reader {
     pub fn new<S: Into<String>>(s: S) -> Self {
-        let text = s.into();
-        let chars = text.chars().collect();
-
         Self {
-            text,
-            chars,
+            chars: s.into().chars().collect(),
             position: 0,
         }
     }
 
+    pub fn error<S: Into<String>>(&self, message: S) -> NbtError {
+        self.error_expected(message, &[])
+    }
+
+    pub fn error_expected<S: Into<String>>(&self, message: S, expected: &[&str]) -> NbtError {
+        let offset = self.position.min(self.chars.len());
+        let line_start = self.chars[..offset].iter()
+            .rposition(|chr| *chr == '\n')
+            .map_or(0, |i| i + 1);
+        let line_end = self.chars[offset..].iter()
+            .position(|chr| *chr == '\n')
+            .map_or(self.chars.len(), |i| offset + i);
+
+        let line = self.chars[..line_start].iter().filter(|chr| **chr == '\n').count() + 1;
+        let column = offset - line_start + 1;
+
+        // clip long lines to a window around the error
+        let start = line_start.max(offset.saturating_sub(CONTEXT));
+        let end = line_end.min(offset + CONTEXT);
+
+        let mut snippet = String::new();
+
+        if start > line_start {
+            snippet.push_str("...");
+        }
+
+        snippet.extend(&self.chars[start..end]);
+
+        if end < line_end {
+            snippet.push_str("...");
+        }
+
+        snippet.push('\n');
+
+        if start > line_start {
+            snippet.push_str("   ");
+        }
+
+        // keep tabs so the caret lines up however they are displayed
+        for chr in &self.chars[start..offset] {
+            snippet.push(if *chr == '\t' { '\t' } else { ' ' });
+        }
+
+        snippet.push('^');
+
+        ParseError {
+            message: message.into(),
+            offset,
+            line,
+            column,
+            expected: expected.iter().map(|s| s.to_string()).collect(),
+            snippet,
+        }.into()
+    }
+
     pub fn position(&self) -> usize {
         self.position
     }
@@ -29,7 +85,7 @@ impl Reader {
     }
 
     pub fn len(&self) -> usize {
-        self.text.len()
+        self.chars.len()
     }
 
     pub fn has_remaining(&self, n: usize) -> bool {
@@ -40,23 +96,23 @@ impl Reader {
         !self.has_remaining(1)
     }
 
-    pub fn peek_nth(&self, n: usize) -> anyhow::Result<char> {
+    pub fn peek_nth(&self, n: usize) -> Result<char, NbtError> {
         self.chars.get(self.position + n)
             .map(|chr| *chr)
-            .ok_or(anyhow::anyhow!("not enough chars"))
+            .ok_or_else(|| self.error("unexpected end of input"))
     }
 
-    pub fn peek(&self) -> anyhow::Result<char> {
+    pub fn peek(&self) -> Result<char, NbtError> {
         self.peek_nth(0)
     }
 
-    pub fn read_nth(&mut self, n: usize) -> anyhow::Result<char> {
+    pub fn read_nth(&mut self, n: usize) -> Result<char, NbtError> {
         let chr = self.peek_nth(n)?;
         self.position += 1 + n;
         Ok(chr)
     }
 
-    pub fn read(&mut self) -> anyhow::Result<char> {
+    pub fn read(&mut self) -> Result<char, NbtError> {
         self.read_nth(0)
     }
 
@@ -64,7 +120,7 @@ impl Reader {
         self.position += 1;
     }
 
-    pub fn skip_whitespace(&mut self) -> anyhow::Result<()> {
+    pub fn skip_whitespace(&mut self) -> Result<(), NbtError> {
         while !self.done() {
             if self.peek()?.is_whitespace() {
                 self.skip();
@@ -76,14 +132,17 @@ impl Reader {
         Ok(())
     }
 
-    pub fn expect(&mut self, chr: char) -> anyhow::Result<()> {
-        let read_chr = self.read()?;
+    pub fn expect(&mut self, chr: char) -> Result<(), NbtError> {
+        let expected = chr.to_string();
 
-        if read_chr != chr {
-            anyhow::bail!("expected {} but got {}", chr, read_chr);
+        match self.peek() {
+            Ok(read_chr) if read_chr == chr => {
+                self.skip();
+                Ok(())
+            },
+            Ok(read_chr) => Err(self.error_expected(format!("unexpected {}", read_chr), &[&expected])),
+            Err(_) => Err(self.error_expected("unexpected end of input", &[&expected])),
         }
-
-        Ok(())
     }
 
     pub fn is_quote(chr: char) -> bool {
@@ -100,17 +159,21 @@ impl Reader {
             || chr == '+'
     }
 
-    pub fn read_unquoted_string(&mut self) -> anyhow::Result<String> {
+    pub fn read_unquoted_string(&mut self) -> Result<String, NbtError> {
+        self.read_while(Self::is_allowed_in_unquoted_string)
+    }
+
+    pub fn read_while<F: Fn(char) -> bool>(&mut self, f: F) -> Result<String, NbtError> {
         let start = self.position;
 
-        while !self.done() && Self::is_allowed_in_unquoted_string(self.peek()?) {
+        while !self.done() && f(self.peek()?) {
             self.skip();
         }
 
-        Ok(String::from(&self.text[start..self.position]))
+        Ok(self.chars[start..self.position].iter().collect())
     }
 
-    pub fn read_quoted_string(&mut self) -> anyhow::Result<String> {
+    pub fn read_quoted_string(&mut self) -> Result<String, NbtError> {
         if self.done() {
             return Ok(String::new());
         }
@@ -118,30 +181,21 @@ impl Reader {
         let chr = self.peek()?;
 
         if !Self::is_quote(chr) {
-            anyhow::bail!("invalid quote char");
+            return Err(self.error("invalid quote char"));
         }
 
         self.skip();
         self.read_string_until(chr)
     }
 
-    pub fn read_string_until(&mut self, chr: char) -> anyhow::Result<String> {
+    pub fn read_string_until(&mut self, chr: char) -> Result<String, NbtError> {
         let mut builder = String::new();
-        let mut escaped = false;
 
         while !self.done() {
             let read_chr = self.read()?;
 
-            if escaped {
-                if read_chr == chr || read_chr == '\\' {
-                    builder.push(read_chr);
-                    escaped = false;
-                } else {
-                    self.position -= 1;
-                    anyhow::bail!("invalid escape in string");
-                }
-            } else if read_chr == '\\' {
-                escaped = true;
+            if read_chr == '\\' {
+                builder.push(self.read_escape()?);
             } else if read_chr == chr {
                 return Ok(builder);
             } else {
@@ -149,10 +203,62 @@ impl Reader {
             }
         }
 
-        Err(anyhow::anyhow!("unexpected end of string"))
+        Err(self.error_expected("unexpected end of input", &[&chr.to_string()]))
+    }
+
+    // like vanilla either quote can be escaped in either kind of string. \N{name} escapes aren't
+    // supported, since they need the unicode name table
+    fn read_escape(&mut self) -> Result<char, NbtError> {
+        let start = self.position;
+
+        let chr = match self.read()? {
+            chr @ ('"' | '\'' | '\\') => chr,
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            's' => ' ',
+            't' => '\t',
+            'x' => self.read_hex_escape(2)?,
+            'u' => self.read_hex_escape(4)?,
+            'U' => self.read_hex_escape(8)?,
+            'N' => {
+                self.position = start;
+                return Err(self.error("named escapes in strings aren't supported"));
+            },
+            _ => {
+                self.position = start;
+                return Err(self.error("invalid escape in string"));
+            },
+        };
+
+        Ok(chr)
+    }
+
+    fn read_hex_escape(&mut self, len: usize) -> Result<char, NbtError> {
+        let start = self.position;
+        let digits: String = self.chars.iter()
+            .skip(start)
+            .take(len)
+            .take_while(|chr| chr.is_ascii_hexdigit())
+            .collect();
+
+        if digits.len() != len {
+            self.position = start + digits.len();
+            return Err(self.error_expected("invalid escape in string", &["hex digit"]));
+        }
+
+        // lone surrogates can't be held in a rust string
+        match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+            Some(chr) => {
+                self.position += len;
+                Ok(chr)
+            },
+            None => Err(self.error(format!("invalid code point {}", digits))),
+        }
     }
 
-    pub fn read_string(&mut self) -> anyhow::Result<String> {
+    pub fn read_string(&mut self) -> Result<String, NbtError> {
         if self.done() {
             Ok(String::new())
         } else {
@@ -0,0 +1,325 @@
+use super::{Tag, Map, Index};
+use super::writer::{quote_and_escape, QuoteStyle};
+use crate::NbtError;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+// renders segments in the syntax read by NbtPath
+pub(crate) fn path_to_string(path: &[PathSegment]) -> String {
+    let mut s = String::new();
+
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !s.is_empty() {
+                    s.push('.');
+                }
+
+                if !key.is_empty() && !key.chars().any(|chr| chr.is_whitespace() || "\"'[]{}.".contains(chr)) {
+                    s.push_str(key);
+                } else {
+                    s.push_str(&quote_and_escape(key, QuoteStyle::Double));
+                }
+            },
+            PathSegment::Index(i) => s.push_str(&format!("[{}]", i)),
+        }
+    }
+
+    s
+}
+
+pub(crate) fn merge_compound(target: &mut Map<String, Tag>, source: &Map<String, Tag>) {
+    for (name, tag) in source {
+        match (target.get_mut(name), tag) {
+            (Some(Tag::Compound(target)), Tag::Compound(source)) => merge_compound(target, source),
+            _ => {
+                target.insert(name.clone(), tag.clone());
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Add {
+        path: Vec<PathSegment>,
+        value: Tag,
+    },
+    Remove {
+        path: Vec<PathSegment>,
+        value: Tag,
+    },
+    Replace {
+        path: Vec<PathSegment>,
+        old: Tag,
+        new: Tag,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &[PathSegment] {
+        match self {
+            Self::Add { path, .. } | Self::Remove { path, .. } | Self::Replace { path, .. } => path,
+        }
+    }
+}
+
+// the changes turning one tag into another, made with Tag::diff
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NbtPatch {
+    pub changes: Vec<Change>,
+}
+
+impl NbtPatch {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    // fails without finishing if a path is missing, so apply to a copy when that matters
+    pub fn apply(&self, tag: &mut Tag) -> Result<(), NbtError> {
+        for change in &self.changes {
+            let path = change.path();
+            let mismatch = || NbtError::PatchMismatch(path_to_string(path));
+
+            let (last, parents) = match path.split_last() {
+                Some(split) => split,
+                None => {
+                    match change {
+                        Change::Replace { new, .. } => *tag = new.clone(),
+                        _ => return Err(mismatch()),
+                    }
+
+                    continue;
+                },
+            };
+
+            let mut parent = &mut *tag;
+
+            for segment in parents {
+                parent = match segment {
+                    PathSegment::Key(key) => key.index_into_mut(parent),
+                    PathSegment::Index(i) => i.index_into_mut(parent),
+                }.ok_or_else(mismatch)?;
+            }
+
+            match (change, last, parent) {
+                (Change::Add { value, .. }, PathSegment::Key(key), Tag::Compound(m)) if !m.contains_key(key) => {
+                    m.insert(key.clone(), value.clone());
+                },
+                (Change::Remove { .. }, PathSegment::Key(key), Tag::Compound(m)) if m.contains_key(key) => {
+                    m.retain(|name, _| name != key);
+                },
+                (Change::Replace { new, .. }, PathSegment::Key(key), parent) => {
+                    *key.index_into_mut(parent).ok_or_else(mismatch)? = new.clone();
+                },
+                (Change::Replace { new, .. }, PathSegment::Index(i), parent) => {
+                    *i.index_into_mut(parent).ok_or_else(mismatch)? = new.clone();
+                },
+                _ => return Err(mismatch()),
+            }
+        }
+
+        Ok(())
+    }
+
+    // renders as an rfc 6902 json patch, where nan and infinity become null
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("[");
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+
+            let (op, value) = match change {
+                Change::Add { value, .. } => ("add", Some(value)),
+                Change::Remove { .. } => ("remove", None),
+                Change::Replace { new, .. } => ("replace", Some(new)),
+            };
+
+            let mut pointer = String::new();
+
+            for segment in change.path() {
+                pointer.push('/');
+
+                match segment {
+                    PathSegment::Key(key) => pointer.push_str(&key.replace('~', "~0").replace('/', "~1")),
+                    PathSegment::Index(i) => pointer.push_str(&i.to_string()),
+                }
+            }
+
+            out.push_str(&format!("{{\"op\":\"{}\",\"path\":", op));
+            write_json_string(&mut out, &pointer);
+
+            if let Some(value) = value {
+                out.push_str(",\"value\":");
+                write_json(&mut out, value);
+            }
+
+            out.push('}');
+        }
+
+        out.push(']');
+        out
+    }
+}
+
+// one change per line, with values as snbt
+impl fmt::Display for NbtPatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            let path = path_to_string(change.path());
+
+            match change {
+                Change::Add { value, .. } => writeln!(f, "+ {}: {}", path, value)?,
+                Change::Remove { value, .. } => writeln!(f, "- {}: {}", path, value)?,
+                Change::Replace { old, new, .. } => writeln!(f, "~ {}: {} -> {}", path, old, new)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+
+    for chr in s.chars() {
+        match chr {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            chr if chr.is_control() => out.push_str(&format!("\\u{:04x}", chr as u32)),
+            chr => out.push(chr),
+        }
+    }
+
+    out.push('"');
+}
+
+fn write_json_float(out: &mut String, value: f64) {
+    if value.is_finite() {
+        out.push_str(&value.to_string());
+    } else {
+        out.push_str("null");
+    }
+}
+
+fn write_json_list<T, F: Fn(&mut String, &T)>(out: &mut String, v: &[T], f: F) {
+    out.push('[');
+
+    for (i, value) in v.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+
+        f(out, value);
+    }
+
+    out.push(']');
+}
+
+fn write_json(out: &mut String, tag: &Tag) {
+    match tag {
+        Tag::End => out.push_str("null"),
+        Tag::Byte(value) => out.push_str(&value.to_string()),
+        Tag::Short(value) => out.push_str(&value.to_string()),
+        Tag::Int(value) => out.push_str(&value.to_string()),
+        Tag::Long(value) => out.push_str(&value.to_string()),
+        Tag::Float(value) => write_json_float(out, *value as f64),
+        Tag::Double(value) => write_json_float(out, *value),
+        Tag::String(s) => write_json_string(out, s),
+        Tag::List(v) => write_json_list(out, v, write_json),
+        Tag::Compound(m) => {
+            out.push('{');
+
+            for (i, (name, tag)) in m.iter().filter(|(_, tag)| **tag != Tag::End).enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+
+                write_json_string(out, name);
+                out.push(':');
+                write_json(out, tag);
+            }
+
+            out.push('}');
+        },
+        Tag::ByteArray(v) => write_json_list(out, v, |out, value| out.push_str(&value.to_string())),
+        Tag::IntArray(v) => write_json_list(out, v, |out, value| out.push_str(&value.to_string())),
+        Tag::LongArray(v) => write_json_list(out, v, |out, value| out.push_str(&value.to_string())),
+    }
+}
+
+fn diff_into(path: &mut Vec<PathSegment>, a: &Tag, b: &Tag, changes: &mut Vec<Change>) {
+    if a == b {
+        return;
+    }
+
+    match (a, b) {
+        (Tag::Compound(ma), Tag::Compound(mb)) => {
+            // keys are visited sorted, so the changes come out in the same order whatever order
+            // the maps keep
+            let mut names: Vec<_> = ma.keys().chain(mb.keys()).collect();
+
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                // end tags are never written, so they count as missing
+                let a = ma.get(name).filter(|tag| **tag != Tag::End);
+                let b = mb.get(name).filter(|tag| **tag != Tag::End);
+
+                path.push(PathSegment::Key(name.clone()));
+
+                match (a, b) {
+                    (Some(a), Some(b)) => diff_into(path, a, b, changes),
+                    (Some(a), None) => changes.push(Change::Remove { path: path.clone(), value: a.clone() }),
+                    (None, Some(b)) => changes.push(Change::Add { path: path.clone(), value: b.clone() }),
+                    (None, None) => {},
+                }
+
+                path.pop();
+            }
+        },
+        // lists are only compared element by element while their length and type stay the same
+        (Tag::List(va), Tag::List(vb)) if va.len() == vb.len() && a.kind() == b.kind() => {
+            for (i, (a, b)) in va.iter().zip(vb).enumerate() {
+                path.push(PathSegment::Index(i));
+                diff_into(path, a, b, changes);
+                path.pop();
+            }
+        },
+        _ => changes.push(Change::Replace {
+            path: path.clone(),
+            old: a.clone(),
+            new: b.clone(),
+        }),
+    }
+}
+
+impl Tag {
+    // vanilla's CompoundTag.merge, recursing into compounds on both sides and replacing the rest
+    pub fn merge(&mut self, other: &Tag) {
+        match (self, other) {
+            (Tag::Compound(target), Tag::Compound(source)) => merge_compound(target, source),
+            (tag, other) => *tag = other.clone(),
+        }
+    }
+
+    // the changes turning a into b, ordered by path
+    pub fn diff(a: &Tag, b: &Tag) -> NbtPatch {
+        let mut changes = vec![];
+
+        diff_into(&mut vec![], a, b, &mut changes);
+
+        NbtPatch { changes }
+    }
+}
@@ -0,0 +1,305 @@
+use super::Tag;
+use crate::NbtError;
+use regex::Regex;
+
+lazy_static! {
+    static ref SIMPLE_PATTERN: Regex = Regex::new("^[A-Za-z0-9._+-]+$").unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuoteStyle {
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    fn chr(&self) -> char {
+        match self {
+            Self::Double => '"',
+            Self::Single => '\'',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnbtOptions {
+    // spaces per nesting level, or none for compact single line output
+    pub indent: Option<usize>,
+    pub sort_keys: bool,
+    // lists and arrays that fit in this many chars stay on one line when indenting
+    pub max_inline_width: usize,
+    // preferred quote, the other is used when it means less escaping
+    pub quote: QuoteStyle,
+    // write bytes 0 and 1 as false and true
+    pub bool_bytes: bool,
+}
+
+impl SnbtOptions {
+    pub fn pretty(indent: usize) -> Self {
+        Self {
+            indent: Some(indent),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for SnbtOptions {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            sort_keys: false,
+            max_inline_width: 80,
+            quote: QuoteStyle::Double,
+            bool_bytes: false,
+        }
+    }
+}
+
+// mirrors java's Float.toString and Double.toString, which vanilla writes, but with rust's
+// shortest round-trip digits so every value reads back to the same bits
+macro_rules! format_float {
+    ($name:ident, $typ:ty) => {
+        pub(crate) fn $name(value: $typ) -> String {
+            if value.is_nan() {
+                return String::from("NaN");
+            } else if value.is_infinite() {
+                return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" });
+            }
+
+            let abs = value.abs();
+            let s = if abs != 0.0 && !(1e-3..1e7).contains(&abs) {
+                format!("{:E}", value)
+            } else {
+                format!("{}", value)
+            };
+
+            // java always has a fraction, so 1 becomes 1.0 and 1E10 becomes 1.0E10
+            match s.find('E') {
+                Some(i) if !s[..i].contains('.') => format!("{}.0{}", &s[..i], &s[i..]),
+                None if !s.contains('.') => format!("{}.0", s),
+                _ => s,
+            }
+        }
+    };
+}
+
+format_float!(format_f32, f32);
+format_float!(format_f64, f64);
+
+pub(crate) fn quote_and_escape(s: &str, quote: QuoteStyle) -> String {
+    let mut builder = String::new();
+    let mut quote_chr = None;
+
+    for chr in s.chars() {
+        // control chars are escaped so they survive being read back
+        let escape = match chr {
+            '\u{8}' => Some('b'),
+            '\u{c}' => Some('f'),
+            '\n' => Some('n'),
+            '\r' => Some('r'),
+            '\t' => Some('t'),
+            _ => None,
+        };
+
+        if let Some(escape) = escape {
+            builder.push('\\');
+            builder.push(escape);
+            continue;
+        } else if chr.is_control() {
+            builder.push_str(&format!("\\u{:04x}", chr as u32));
+            continue;
+        }
+
+        if chr == '\\' {
+            builder.push('\\');
+        } else if chr == '"' || chr == '\'' {
+            // use whichever quote appears first in the string as the escaped one
+            if quote_chr.is_none() {
+                quote_chr = Some(if chr == '"' { '\'' } else { '"' });
+            }
+
+            if quote_chr.is_some() && quote_chr.unwrap() == chr {
+                builder.push('\\');
+            }
+        }
+
+        builder.push(chr);
+    }
+
+    let quote_chr = quote_chr.unwrap_or_else(|| quote.chr());
+
+    builder.insert(0, quote_chr);
+    builder.push(quote_chr);
+    builder
+}
+
+pub(crate) struct Writer<'a> {
+    options: &'a SnbtOptions,
+    end_marker: bool,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(options: &'a SnbtOptions) -> Self {
+        Self { options, end_marker: false }
+    }
+
+    // writes end tags as END like vanilla's StringTagVisitor instead of failing, for display
+    pub fn with_end_marker(mut self) -> Self {
+        self.end_marker = true;
+        self
+    }
+
+    pub fn write(&self, tag: &Tag) -> Result<String, NbtError> {
+        let mut out = String::new();
+
+        self.write_tag(&mut out, tag, 0, self.options.indent.is_some())?;
+
+        Ok(out)
+    }
+
+    fn key(&self, name: &str) -> String {
+        if SIMPLE_PATTERN.is_match(name) {
+            name.to_owned()
+        } else {
+            quote_and_escape(name, self.options.quote)
+        }
+    }
+
+    fn newline(&self, out: &mut String, depth: usize) {
+        out.push('\n');
+
+        for _ in 0..depth * self.options.indent.unwrap_or(0) {
+            out.push(' ');
+        }
+    }
+
+    // writes items either on one line or, when pretty, one per line
+    fn write_items(
+        &self,
+        out: &mut String,
+        open: &str,
+        close: &str,
+        items: Vec<String>,
+        depth: usize,
+        multiline: bool,
+    ) {
+        out.push_str(open);
+
+        if multiline && !items.is_empty() {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+
+                self.newline(out, depth + 1);
+                out.push_str(item);
+            }
+
+            self.newline(out, depth);
+        } else {
+            let separator = if self.options.indent.is_some() { ", " } else { "," };
+
+            out.push_str(&items.join(separator));
+        }
+
+        out.push_str(close);
+    }
+
+    fn write_list(
+        &self,
+        out: &mut String,
+        open: &str,
+        tags: &[Tag],
+        depth: usize,
+        pretty: bool,
+    ) -> Result<(), NbtError> {
+        let inline = self.render(tags, depth, false)?;
+        let width: usize = open.len() + inline.iter().map(|item| item.len() + 2).sum::<usize>();
+
+        if pretty && width > self.options.max_inline_width {
+            let items = self.render(tags, depth + 1, true)?;
+
+            self.write_items(out, open, "]", items, depth, true);
+        } else {
+            self.write_items(out, open, "]", inline, depth, false);
+        }
+
+        Ok(())
+    }
+
+    fn render(&self, tags: &[Tag], depth: usize, pretty: bool) -> Result<Vec<String>, NbtError> {
+        let mut items = vec![];
+
+        for tag in tags {
+            let mut item = String::new();
+
+            self.write_tag(&mut item, tag, depth, pretty)?;
+            items.push(item);
+        }
+
+        Ok(items)
+    }
+
+    fn write_tag(&self, out: &mut String, tag: &Tag, depth: usize, pretty: bool) -> Result<(), NbtError> {
+        match tag {
+            Tag::End if self.end_marker => out.push_str("END"),
+            Tag::End => return Err(NbtError::EncodeEnd),
+
+            Tag::Byte(value) if self.options.bool_bytes && (*value == 0 || *value == 1) => {
+                out.push_str(if *value == 1 { "true" } else { "false" });
+            },
+            Tag::Byte(value) => out.push_str(&format!("{}b", value)),
+            Tag::Short(value) => out.push_str(&format!("{}s", value)),
+            Tag::Int(value) => out.push_str(&format!("{}", value)),
+            Tag::Long(value) => out.push_str(&format!("{}L", value)),
+
+            Tag::Float(value) => out.push_str(&format!("{}f", format_f32(*value))),
+            Tag::Double(value) => out.push_str(&format!("{}d", format_f64(*value))),
+
+            Tag::String(s) => out.push_str(&quote_and_escape(s, self.options.quote)),
+
+            Tag::List(v) => self.write_list(out, "[", v, depth, pretty)?,
+
+            Tag::Compound(m) => {
+                let mut entries: Vec<_> = m.iter()
+                    .filter(|(_, tag)| **tag != Tag::End)
+                    .collect();
+
+                if self.options.sort_keys {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+
+                let separator = if self.options.indent.is_some() { ": " } else { ":" };
+                let mut items = vec![];
+
+                for (name, tag) in entries {
+                    let mut item = self.key(name);
+
+                    item.push_str(separator);
+                    self.write_tag(&mut item, tag, depth + 1, pretty)?;
+                    items.push(item);
+                }
+
+                self.write_items(out, "{", "}", items, depth, pretty);
+            },
+
+            Tag::ByteArray(v) => {
+                let tags: Vec<_> = v.iter().map(|value| Tag::Byte(*value)).collect();
+
+                self.write_list(out, "[B;", &tags, depth, pretty)?;
+            },
+            Tag::IntArray(v) => {
+                let tags: Vec<_> = v.iter().map(|value| Tag::Int(*value)).collect();
+
+                self.write_list(out, "[I;", &tags, depth, pretty)?;
+            },
+            Tag::LongArray(v) => {
+                let tags: Vec<_> = v.iter().map(|value| Tag::Long(*value)).collect();
+
+                self.write_list(out, "[L;", &tags, depth, pretty)?;
+            },
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,684 @@
+use crate::{Nbt, Tag, List, Map, NbtError, DecodeError};
+use crate::tag::Number;
+use crate::mutf8;
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+// matches the default decoder limit, so skipping can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+// payload sizes of the tags that have a fixed one
+fn fixed_size(id: u8) -> Option<usize> {
+    match id {
+        0 => Some(0),
+        1 => Some(1),
+        2 => Some(2),
+        3 | 5 => Some(4),
+        4 | 6 => Some(8),
+        _ => None,
+    }
+}
+
+fn bytes(data: &[u8], pos: usize, len: usize) -> Result<&[u8], DecodeError> {
+    data.get(pos..)
+        .and_then(|rest| rest.get(..len))
+        .ok_or(DecodeError::UnexpectedEof)
+}
+
+macro_rules! read_num {
+    ($name:ident, $typ:ty) => {
+        fn $name(data: &[u8], pos: usize) -> Result<$typ, DecodeError> {
+            let mut buf = [0; std::mem::size_of::<$typ>()];
+
+            buf.copy_from_slice(bytes(data, pos, std::mem::size_of::<$typ>())?);
+
+            Ok(<$typ>::from_be_bytes(buf))
+        }
+    };
+}
+
+read_num!(read_u8, u8);
+read_num!(read_i8, i8);
+read_num!(read_u16, u16);
+read_num!(read_i16, i16);
+read_num!(read_i32, i32);
+read_num!(read_i64, i64);
+read_num!(read_f32, f32);
+read_num!(read_f64, f64);
+
+fn read_len(data: &[u8], pos: usize) -> Result<usize, DecodeError> {
+    match read_i32(data, pos)? {
+        len if len < 0 => Err(DecodeError::NegativeLength(len)),
+        len => Ok(len as usize),
+    }
+}
+
+// a string's bytes as (start, len), after its length prefix
+fn read_string(data: &[u8], pos: usize) -> Result<(usize, usize), DecodeError> {
+    let len = read_u16(data, pos)? as usize;
+
+    bytes(data, pos + 2, len)?;
+
+    Ok((pos + 2, len))
+}
+
+// returns the position just past a payload without looking at anything it doesn't have to
+fn skip(data: &[u8], pos: usize, id: u8, depth: usize) -> Result<usize, DecodeError> {
+    if let Some(size) = fixed_size(id) {
+        bytes(data, pos, size)?;
+
+        return Ok(pos + size);
+    }
+
+    match id {
+        8 => {
+            let (start, len) = read_string(data, pos)?;
+
+            Ok(start + len)
+        },
+        7 | 11 | 12 => {
+            let size = match id {
+                7 => 1,
+                11 => 4,
+                _ => 8,
+            };
+
+            let len = read_len(data, pos)?
+                .checked_mul(size)
+                .ok_or(DecodeError::UnexpectedEof)?;
+
+            bytes(data, pos + 4, len)?;
+
+            Ok(pos + 4 + len)
+        },
+        9 => {
+            if depth >= MAX_DEPTH {
+                return Err(DecodeError::DepthLimit(MAX_DEPTH));
+            }
+
+            let id = read_u8(data, pos)?;
+            let len = read_len(data, pos + 1)?;
+            let mut pos = pos + 5;
+
+            if id == 0 && len > 0 {
+                return Err(DecodeError::InvalidList);
+            }
+
+            // lists of numbers are skipped in one step
+            if let Some(size) = fixed_size(id) {
+                let len = len.checked_mul(size).ok_or(DecodeError::UnexpectedEof)?;
+
+                bytes(data, pos, len)?;
+
+                return Ok(pos + len);
+            }
+
+            for _ in 0..len {
+                pos = skip(data, pos, id, depth + 1)?;
+            }
+
+            Ok(pos)
+        },
+        10 => {
+            if depth >= MAX_DEPTH {
+                return Err(DecodeError::DepthLimit(MAX_DEPTH));
+            }
+
+            let mut pos = pos;
+
+            loop {
+                let id = read_u8(data, pos)?;
+
+                if id == 0 {
+                    return Ok(pos + 1);
+                }
+
+                let (start, len) = read_string(data, pos + 1)?;
+
+                pos = skip(data, start + len, id, depth + 1)?;
+            }
+        },
+        _ => Err(DecodeError::UnknownId(id)),
+    }
+}
+
+fn read<'a>(data: &'a [u8], pos: usize, id: u8) -> Result<TagRef<'a>, DecodeError> {
+    Ok(match id {
+        0 => TagRef::End,
+
+        1 => TagRef::Byte(read_i8(data, pos)?),
+        2 => TagRef::Short(read_i16(data, pos)?),
+        3 => TagRef::Int(read_i32(data, pos)?),
+        4 => TagRef::Long(read_i64(data, pos)?),
+
+        5 => TagRef::Float(read_f32(data, pos)?),
+        6 => TagRef::Double(read_f64(data, pos)?),
+
+        8 => {
+            let (start, len) = read_string(data, pos)?;
+
+            TagRef::String(StrRef { data, pos: start, len })
+        },
+
+        9 => TagRef::List(ListRef {
+            data,
+            pos: pos + 5,
+            id: read_u8(data, pos)?,
+            len: read_len(data, pos + 1)?,
+        }),
+        10 => TagRef::Compound(CompoundRef { data, pos }),
+
+        7 => TagRef::ByteArray(ByteArrayRef(bytes(data, pos + 4, read_len(data, pos)?)?)),
+        11 => TagRef::IntArray(IntArrayRef(bytes(data, pos + 4, read_len(data, pos)?.saturating_mul(4))?)),
+        12 => TagRef::LongArray(LongArrayRef(bytes(data, pos + 4, read_len(data, pos)?.saturating_mul(8))?)),
+
+        _ => return Err(DecodeError::UnknownId(id)),
+    })
+}
+
+fn decode_error(kind: DecodeError, offset: usize) -> NbtError {
+    NbtError::Decode {
+        kind,
+        offset: offset as u64,
+        path: String::new(),
+    }
+}
+
+// a string as stored, in java's modified utf-8
+#[derive(Clone, Copy)]
+pub struct StrRef<'a> {
+    data: &'a [u8],
+    pos: usize,
+    len: usize,
+}
+
+impl<'a> StrRef<'a> {
+    pub fn as_bytes(&self) -> &'a [u8] {
+        &self.data[self.pos..self.pos + self.len]
+    }
+
+    // borrows unless the string has nul or supplementary characters
+    pub fn to_str(&self) -> Option<Cow<'a, str>> {
+        match str::from_utf8(self.as_bytes()) {
+            Ok(s) => Some(Cow::Borrowed(s)),
+            Err(_) => mutf8::decode(self.as_bytes()).map(Cow::Owned),
+        }
+    }
+
+    fn decode(self) -> Result<String, NbtError> {
+        self.to_str()
+            .map(Cow::into_owned)
+            .ok_or_else(|| decode_error(DecodeError::InvalidString, self.pos))
+    }
+}
+
+impl PartialEq for StrRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl PartialEq<str> for StrRef<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_bytes() == &mutf8::encode(other)[..]
+    }
+}
+
+impl PartialEq<&str> for StrRef<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        *self == **other
+    }
+}
+
+impl fmt::Debug for StrRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_str() {
+            Some(s) => write!(f, "{:?}", s),
+            None => write!(f, "{:?}", self.as_bytes()),
+        }
+    }
+}
+
+macro_rules! array_ref {
+    ($name:ident, $typ:ty) => {
+        // an array read straight from the big-endian bytes
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        pub struct $name<'a>(&'a [u8]);
+
+        impl<'a> $name<'a> {
+            const SIZE: usize = std::mem::size_of::<$typ>();
+
+            pub fn len(&self) -> usize {
+                self.0.len() / Self::SIZE
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+
+            pub fn get(&self, i: usize) -> Option<$typ> {
+                self.0.get(i * Self::SIZE..(i + 1) * Self::SIZE).map(|bytes| {
+                    let mut buf = [0; std::mem::size_of::<$typ>()];
+
+                    buf.copy_from_slice(bytes);
+                    <$typ>::from_be_bytes(buf)
+                })
+            }
+
+            pub fn iter(&self) -> impl Iterator<Item = $typ> + 'a {
+                self.0.chunks_exact(Self::SIZE).map(|bytes| {
+                    let mut buf = [0; std::mem::size_of::<$typ>()];
+
+                    buf.copy_from_slice(bytes);
+                    <$typ>::from_be_bytes(buf)
+                })
+            }
+
+            pub fn to_vec(&self) -> Vec<$typ> {
+                self.iter().collect()
+            }
+        }
+    };
+}
+
+array_ref!(ByteArrayRef, i8);
+array_ref!(IntArrayRef, i32);
+array_ref!(LongArrayRef, i64);
+
+#[derive(Clone, Copy)]
+pub struct ListRef<'a> {
+    data: &'a [u8],
+    pos: usize,
+    id: u8,
+    len: usize,
+}
+
+impl<'a> ListRef<'a> {
+    pub fn element_id(&self) -> u8 {
+        self.id
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // elements with a fixed size are found directly, anything else skips the ones before it
+    pub fn get(&self, i: usize) -> Option<TagRef<'a>> {
+        if i >= self.len {
+            return None;
+        }
+
+        match fixed_size(self.id) {
+            Some(size) => read(self.data, self.pos + i * size, self.id).ok(),
+            None => self.iter().nth(i),
+        }
+    }
+
+    pub fn iter(&self) -> ListIter<'a> {
+        ListIter {
+            list: *self,
+            pos: Some(self.pos),
+            remaining: self.len,
+        }
+    }
+}
+
+impl fmt::Debug for ListRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for ListRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<'a> IntoIterator for ListRef<'a> {
+    type Item = TagRef<'a>;
+    type IntoIter = ListIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// stops early if the data turns out to be malformed
+pub struct ListIter<'a> {
+    list: ListRef<'a>,
+    pos: Option<usize>,
+    remaining: usize,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = TagRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.pos?;
+
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let tag = read(self.list.data, pos, self.list.id).ok()?;
+
+        self.remaining -= 1;
+        self.pos = skip(self.list.data, pos, self.list.id, 0).ok();
+
+        Some(tag)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct CompoundRef<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> CompoundRef<'a> {
+    // compares raw names, so nothing is decoded or allocated for the entries skipped over
+    pub fn get(&self, name: &str) -> Option<TagRef<'a>> {
+        let name = mutf8::encode(name);
+
+        self.iter()
+            .find(|(key, _)| key.as_bytes() == &name[..])
+            .map(|(_, tag)| tag)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn iter(&self) -> CompoundIter<'a> {
+        CompoundIter {
+            data: self.data,
+            pos: Some(self.pos),
+        }
+    }
+}
+
+impl fmt::Debug for CompoundRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for CompoundRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<'a> IntoIterator for CompoundRef<'a> {
+    type Item = (StrRef<'a>, TagRef<'a>);
+    type IntoIter = CompoundIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// entries in the order they are stored, stopping early if the data turns out to be malformed
+pub struct CompoundIter<'a> {
+    data: &'a [u8],
+    pos: Option<usize>,
+}
+
+impl<'a> Iterator for CompoundIter<'a> {
+    type Item = (StrRef<'a>, TagRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let pos = self.pos.take()?;
+        let id = read_u8(self.data, pos).ok()?;
+
+        if id == 0 {
+            return None;
+        }
+
+        let (start, len) = read_string(self.data, pos + 1).ok()?;
+        let tag = read(self.data, start + len, id).ok()?;
+
+        self.pos = skip(self.data, start + len, id, 0).ok();
+
+        Some((StrRef { data: self.data, pos: start, len }, tag))
+    }
+}
+
+// a tag borrowed from encoded java nbt, where lists and compounds are only read as far as needed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagRef<'a> {
+    End,
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(ByteArrayRef<'a>),
+    String(StrRef<'a>),
+    List(ListRef<'a>),
+    Compound(CompoundRef<'a>),
+    IntArray(IntArrayRef<'a>),
+    LongArray(LongArrayRef<'a>),
+}
+
+impl<'a> TagRef<'a> {
+    pub fn id(&self) -> u8 {
+        match self {
+            Self::End => 0,
+            Self::Byte(_) => 1,
+            Self::Short(_) => 2,
+            Self::Int(_) => 3,
+            Self::Long(_) => 4,
+            Self::Float(_) => 5,
+            Self::Double(_) => 6,
+            Self::ByteArray(_) => 7,
+            Self::String(_) => 8,
+            Self::List(_) => 9,
+            Self::Compound(_) => 10,
+            Self::IntArray(_) => 11,
+            Self::LongArray(_) => 12,
+        }
+    }
+
+    pub fn get<I: RefIndex>(&self, index: I) -> Option<TagRef<'a>> {
+        index.index_ref(self)
+    }
+
+    // like indexing a Tag, gives an end tag when nothing is found so lookups can be chained
+    pub fn index<I: RefIndex>(&self, index: I) -> TagRef<'a> {
+        self.get(index).unwrap_or(TagRef::End)
+    }
+
+    fn number(&self) -> Option<Number> {
+        match *self {
+            Self::Byte(value) => Some(Number::Byte(value)),
+            Self::Short(value) => Some(Number::Short(value)),
+            Self::Int(value) => Some(Number::Int(value)),
+            Self::Long(value) => Some(Number::Long(value)),
+            Self::Float(value) => Some(Number::Float(value)),
+            Self::Double(value) => Some(Number::Double(value)),
+            _ => None,
+        }
+    }
+
+    // converted the same way as Tag's as_ methods
+    pub fn as_i32(&self) -> Option<i32> {
+        self.number().map(Number::as_i32)
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.number().map(Number::as_i64)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.number().map(Number::as_f64)
+    }
+
+    pub fn as_str(&self) -> Option<Cow<'a, str>> {
+        match self {
+            Self::String(s) => s.to_str(),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<ListRef<'a>> {
+        match self {
+            Self::List(list) => Some(*list),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<CompoundRef<'a>> {
+        match self {
+            Self::Compound(compound) => Some(*compound),
+            _ => None,
+        }
+    }
+
+    fn payload(&self) -> Option<(&'a [u8], usize)> {
+        match self {
+            Self::List(list) => Some((list.data, list.pos - 5)),
+            Self::Compound(compound) => Some((compound.data, compound.pos)),
+            _ => None,
+        }
+    }
+
+    // navigation treats malformed data as missing, this checks the whole subtree up front
+    pub fn validate(&self) -> Result<(), NbtError> {
+        match self.payload() {
+            Some((data, pos)) => skip(data, pos, self.id(), 0)
+                .map(|_| ())
+                .map_err(|kind| decode_error(kind, pos)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn to_tag(&self) -> Result<Tag, NbtError> {
+        self.validate()?;
+        self.convert()
+    }
+
+    // only strings can still fail once the structure is validated
+    fn convert(&self) -> Result<Tag, NbtError> {
+        Ok(match self {
+            Self::End => Tag::End,
+            Self::Byte(value) => Tag::Byte(*value),
+            Self::Short(value) => Tag::Short(*value),
+            Self::Int(value) => Tag::Int(*value),
+            Self::Long(value) => Tag::Long(*value),
+            Self::Float(value) => Tag::Float(*value),
+            Self::Double(value) => Tag::Double(*value),
+            Self::String(s) => Tag::String(s.decode()?),
+            Self::List(list) => {
+                let tags = list.iter().map(|tag| tag.convert()).collect::<Result<_, _>>()?;
+
+                Tag::List(List::from_tags_unchecked(list.id, tags))
+            },
+            Self::Compound(compound) => {
+                let mut m = Map::new();
+
+                for (name, tag) in compound.iter() {
+                    m.insert(name.decode()?, tag.convert()?);
+                }
+
+                Tag::Compound(m)
+            },
+            Self::ByteArray(v) => Tag::ByteArray(v.to_vec()),
+            Self::IntArray(v) => Tag::IntArray(v.to_vec()),
+            Self::LongArray(v) => Tag::LongArray(v.to_vec()),
+        })
+    }
+}
+
+pub trait RefIndex {
+    fn index_ref<'a>(&self, tag: &TagRef<'a>) -> Option<TagRef<'a>>;
+}
+
+impl RefIndex for str {
+    fn index_ref<'a>(&self, tag: &TagRef<'a>) -> Option<TagRef<'a>> {
+        tag.as_compound()?.get(self)
+    }
+}
+
+impl RefIndex for String {
+    fn index_ref<'a>(&self, tag: &TagRef<'a>) -> Option<TagRef<'a>> {
+        self[..].index_ref(tag)
+    }
+}
+
+impl RefIndex for usize {
+    fn index_ref<'a>(&self, tag: &TagRef<'a>) -> Option<TagRef<'a>> {
+        tag.as_list()?.get(*self)
+    }
+}
+
+impl<T: ?Sized + RefIndex> RefIndex for &T {
+    fn index_ref<'a>(&self, tag: &TagRef<'a>) -> Option<TagRef<'a>> {
+        (**self).index_ref(tag)
+    }
+}
+
+// uncompressed java nbt borrowed from a buffer, for pulling a few values out without decoding
+// everything, as with Region::read_chunk_data
+#[derive(Debug, Clone, Copy)]
+pub struct NbtView<'a> {
+    name: StrRef<'a>,
+    tag: TagRef<'a>,
+}
+
+impl<'a> NbtView<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, NbtError> {
+        let id = read_u8(data, 0).map_err(|kind| decode_error(kind, 0))?;
+
+        if id == 0 {
+            return Ok(Self { name: StrRef { data, pos: 0, len: 0 }, tag: TagRef::End });
+        }
+
+        let (start, len) = read_string(data, 1).map_err(|kind| decode_error(kind, 1))?;
+        let tag = read(data, start + len, id).map_err(|kind| decode_error(kind, start + len))?;
+
+        Ok(Self {
+            name: StrRef { data, pos: start, len },
+            tag,
+        })
+    }
+
+    // the network variant used by the protocol since 1.20.2 has no root name
+    pub fn network(data: &'a [u8]) -> Result<Self, NbtError> {
+        let id = read_u8(data, 0).map_err(|kind| decode_error(kind, 0))?;
+        let tag = read(data, 1, id).map_err(|kind| decode_error(kind, 1))?;
+
+        Ok(Self { name: StrRef { data, pos: 0, len: 0 }, tag })
+    }
+
+    pub fn name(&self) -> StrRef<'a> {
+        self.name
+    }
+
+    pub fn tag(&self) -> TagRef<'a> {
+        self.tag
+    }
+
+    pub fn get<I: RefIndex>(&self, index: I) -> Option<TagRef<'a>> {
+        self.tag.get(index)
+    }
+
+    pub fn index<I: RefIndex>(&self, index: I) -> TagRef<'a> {
+        self.tag.index(index)
+    }
+
+    pub fn validate(&self) -> Result<(), NbtError> {
+        self.tag.validate()
+    }
+
+    pub fn to_nbt(&self) -> Result<Nbt, NbtError> {
+        Ok(Nbt::new(self.name.decode()?, self.tag.to_tag()?))
+    }
+}
@@ -2,6 +2,7 @@ use thiserror::Error;
 use bytes::{Buf, BufMut};
 use crate::FromJson;
 use crate::chat::Component;
+use blocky_nbt::{Nbt, Tag, NbtError, DecodeError};
 
 #[derive(Error, Debug)]
 pub enum ProtocolError {
@@ -434,6 +435,110 @@ impl<T: Component> ProtocolWrite for T {
     }
 }
 
+fn nbt_error(err: NbtError) -> ProtocolError {
+    match err {
+        NbtError::Decode { kind: DecodeError::UnexpectedEof, .. } => ProtocolError::NotEnoughBytes,
+        NbtError::Decode { kind: DecodeError::SizeLimit(_), .. } => ProtocolError::TooLarge,
+        _ => ProtocolError::Invalid,
+    }
+}
+
+fn encode_tag(tag: &Tag) -> Result<Vec<u8>, ProtocolError> {
+    let mut bytes = vec![];
+
+    tag.encode_network(&mut bytes).map_err(nbt_error)?;
+
+    Ok(bytes)
+}
+
+// nbt fields use the nameless network variant, so any root name is dropped
+impl ProtocolLength for Tag {
+    fn len(&self) -> usize {
+        self.network_len()
+    }
+}
+
+impl ProtocolRead for Tag {
+    fn read<U: Buf>(src: &mut U) -> Result<Self, ProtocolError> {
+        Ok(Nbt::decode_network(&mut src.reader()).map_err(nbt_error)?.tag)
+    }
+}
+
+impl ProtocolWrite for Tag {
+    fn write<U: BufMut>(&self, dst: &mut U) -> Result<(), ProtocolError> {
+        let bytes = encode_tag(self)?;
+
+        if dst.remaining_mut() < bytes.len() {
+            Err(ProtocolError::NotEnoughBytes)
+        } else {
+            dst.put_slice(&bytes);
+            Ok(())
+        }
+    }
+}
+
+impl ProtocolLength for Nbt {
+    fn len(&self) -> usize {
+        self.tag.len()
+    }
+}
+
+impl ProtocolRead for Nbt {
+    fn read<U: Buf>(src: &mut U) -> Result<Self, ProtocolError> {
+        Ok(Nbt::new(String::new(), Tag::read(src)?))
+    }
+}
+
+impl ProtocolWrite for Nbt {
+    fn write<U: BufMut>(&self, dst: &mut U) -> Result<(), ProtocolError> {
+        self.tag.write(dst)
+    }
+}
+
 protocol_struct!(Test {
     text: crate::chat::TextComponent,
-});
\ No newline at end of file
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blocky_nbt::tag;
+
+    fn round_trip<T: ProtocolRead + ProtocolWrite>(value: &T) -> T {
+        let mut bytes = vec![];
+
+        value.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), value.len());
+
+        let mut src = &bytes[..];
+        let read = T::read(&mut src).unwrap();
+
+        assert!(src.is_empty(), "read left {} bytes", src.len());
+
+        read
+    }
+
+    #[test]
+    fn tag() {
+        let tag = tag!("{name: 'Jaden \u{1f600}', nul: '\0', health: 20.0f, pos: [1.0d, 2.0d], empty: [], nested: {a: [B; 1b, 2b], b: [I; 3], c: [L; 4L]}}");
+
+        assert_eq!(round_trip(&tag), tag);
+        assert_eq!(round_trip(&Tag::End), Tag::End);
+        assert_eq!(round_trip(&Tag::Int(5)), Tag::Int(5));
+        assert_eq!(round_trip(&Nbt::new(String::new(), tag.clone())).tag, tag);
+    }
+
+    #[test]
+    fn tag_errors() {
+        let long = Tag::String("a".repeat(70000));
+
+        assert_eq!(long.len(), 70003);
+        assert!(matches!(long.write(&mut vec![]), Err(ProtocolError::Invalid)));
+
+        let mut bytes = vec![];
+
+        tag!("{a: 1, b: 'text'}").write(&mut bytes).unwrap();
+
+        assert!(matches!(Tag::read(&mut &bytes[..bytes.len() - 2]), Err(ProtocolError::NotEnoughBytes)));
+    }
+}